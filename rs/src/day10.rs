@@ -1,3 +1,4 @@
+use crate::netpbm::join;
 use itertools::Itertools;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
@@ -5,6 +6,9 @@ use nom::character::complete::{i32 as nom_i32, newline};
 use nom::multi::separated_list1;
 use nom::sequence::preceded;
 use nom::IResult;
+use ocr::OcrError;
+use std::collections::HashSet;
+use std::iter::repeat_n;
 use yaah::*;
 
 #[aoc_generator(day10)]
@@ -13,76 +17,305 @@ fn gen(input: &'static str) -> Vec<Instruction> {
 }
 
 #[aoc(day10, part1)]
-fn solve_part1(instructions: &Vec<Instruction>) -> i32 {
-	let mut x = 1;
-	let mut cycle = 1;
-	let mut signal_strength = 0;
-	for instruction in instructions {
-		signal_strength = boost_signal(signal_strength, cycle, x);
-		if let Instruction::Addx(v) = instruction {
-			cycle += 1;
-			signal_strength = boost_signal(signal_strength, cycle, x);
-			cycle += 1;
-			x += v;
-		} else {
-			cycle += 1;
-		}
-	}
-	signal_strength
+fn solve_part1(instructions: &[Instruction]) -> i32 {
+	Crt::default().signal_strength(instructions)
 }
 
 #[aoc(day10, part2)]
-fn solve_part2(instructions: &Vec<Instruction>) -> String {
-	let mut cycle = 1;
-	let mut sprite = 0;
-	let mut image: Vec<char> = vec![];
-	for instruction in instructions {
-		image.push(pixel(cycle, sprite));
-		if let Instruction::Addx(v) = instruction {
-			cycle += 1;
-			image.push(pixel(cycle, sprite));
-			cycle += 1;
-			sprite += v;
-		} else {
-			cycle += 1;
+fn solve_part2(instructions: &[Instruction]) -> String {
+	Crt::default().render(instructions)
+}
+
+/// Reads the letters off the screen instead of leaving that to a human
+#[aoc(day10, part2, ocr)]
+fn solve_part2_ocr(instructions: &[Instruction]) -> Result<String, OcrError> {
+	let reading = ocr::read_keeping_image(&Crt::default().render(instructions))?;
+	#[cfg(feature = "debug")]
+	println!("{}", reading.image);
+	Ok(reading.text)
+}
+
+/// Screen geometry, sprite and signal sampling of the device
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Crt {
+	pub width: usize,
+	pub height: usize,
+	/// Pixels covered by the sprite, centred on X
+	pub sprite_width: usize,
+	/// First cycle the signal strength is sampled during
+	pub first_sample: u32,
+	/// Cycles between samples after the first, zero to only sample once
+	pub sample_every: u32,
+}
+
+impl Default for Crt {
+	fn default() -> Self {
+		Crt {
+			width: 40,
+			height: 6,
+			sprite_width: 3,
+			first_sample: 20,
+			sample_every: 40,
 		}
 	}
-	image
-		.chunks(40)
-		.map(|line| line.iter().collect::<String>())
-		.join("\n")
 }
 
-#[cfg(feature = "debug")]
-fn draw(cycle: i32, sprite: i32) {
-	let n = cycle % 40;
-	let position = (cycle - 1) % 40;
-	let c = match lit(position, sprite) {
-		true => '#',
-		false => '.',
-	};
-	match n {
-		0 => print!("{c}\n"),
-		_ => print!("{c}"),
+impl Crt {
+	pub fn is_sampled(&self, cycle: u32) -> bool {
+		cycle >= self.first_sample && (cycle - self.first_sample).is_multiple_of(self.sample_every)
+	}
+
+	/// Sum of cycle times X over the sampled cycles
+	pub fn signal_strength<I: Execute>(&self, instructions: &[I]) -> i32 {
+		Cpu::new(instructions)
+			.filter(|(cycle, _)| self.is_sampled(*cycle))
+			.map(|(cycle, x)| cycle as i32 * x)
+			.sum()
+	}
+
+	/// Whether the sprite at `x` covers `column`
+	pub fn lit(&self, column: usize, x: i32) -> bool {
+		let left = x - (self.sprite_width as i32 - 1) / 2;
+		(left..left + self.sprite_width as i32).contains(&(column as i32))
+	}
+
+	/// Lit pixels row by row, anything the program doesn't reach stays dark
+	pub fn screen<I: Execute>(&self, instructions: &[I]) -> Vec<Vec<bool>> {
+		let mut screen = vec![vec![false; self.width]; self.height];
+		Cpu::new(instructions)
+			.take(self.width * self.height)
+			.for_each(|(cycle, x)| {
+				let pixel = cycle as usize - 1;
+				let column = pixel % self.width;
+				screen[pixel / self.width][column] = self.lit(column, x);
+			});
+		screen
+	}
+
+	/// The screen as lines of `#` and `.`
+	pub fn render<I: Execute>(&self, instructions: &[I]) -> String {
+		self.screen(instructions)
+			.iter()
+			.map(|row| {
+				row.iter()
+					.map(|lit| if *lit { '#' } else { '.' })
+					.collect::<String>()
+			})
+			.join("\n")
+	}
+
+	/// The screen as a plain PBM image, where lit pixels are black
+	pub fn pbm<I: Execute>(&self, instructions: &[I]) -> String {
+		let rows = self
+			.screen(instructions)
+			.iter()
+			.map(|row| join(row.iter().map(|lit| *lit as u8)))
+			.join("\n");
+		format!("P1\n{} {}\n{rows}\n", self.width, self.height)
+	}
+
+	/// The screen as a greyscale PNG with lit pixels white, each pixel drawn as a `scale` sized square
+	pub fn png<I: Execute>(
+		&self,
+		instructions: &[I],
+		scale: usize,
+	) -> Result<Vec<u8>, png::EncodingError> {
+		let scale = scale.max(1);
+		let pixels: Vec<u8> = self
+			.screen(instructions)
+			.iter()
+			.flat_map(|row| {
+				let scanline: Vec<u8> = row
+					.iter()
+					.flat_map(|lit| repeat_n(if *lit { 0xff } else { 0 }, scale))
+					.collect();
+				repeat_n(scanline, scale).flatten()
+			})
+			.collect();
+
+		let mut png = vec![];
+		let mut encoder = png::Encoder::new(
+			&mut png,
+			(self.width * scale) as u32,
+			(self.height * scale) as u32,
+		);
+		encoder.set_color(png::ColorType::Grayscale);
+		encoder.set_depth(png::BitDepth::Eight);
+		let mut writer = encoder.write_header()?;
+		writer.write_image_data(&pixels)?;
+		writer.finish()?;
+		Ok(png)
+	}
+}
+
+/// Something the [`Cpu`] can run, taking effect at the end of its last cycle
+pub trait Execute {
+	/// Cycles taken to complete, at least one
+	fn cycles(&self) -> u32;
+	fn execute(&self, registers: &mut Registers);
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Register {
+	X,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Registers {
+	pub x: i32,
+}
+
+impl Default for Registers {
+	fn default() -> Self {
+		Registers { x: 1 }
 	}
 }
 
-fn pixel(cycle: i32, sprite: i32) -> char {
-	let position = (cycle - 1) % 40;
-	match lit(position, sprite) {
-		true => '#',
-		false => '.',
+impl Registers {
+	pub fn get(&self, register: Register) -> i32 {
+		match register {
+			Register::X => self.x,
+		}
 	}
 }
 
-fn lit(n: i32, sprite: i32) -> bool {
-	((sprite)..(sprite + 3)).contains(&n)
+/// Why [`Cpu::run`] returned
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stop {
+	Halted,
+	/// The breakpoint's cycle has just run
+	Breakpoint(u32),
+	/// A watched register changed at the end of `cycle`
+	Watch {
+		cycle: u32,
+		register: Register,
+		old: i32,
+		new: i32,
+	},
+}
+
+/// Runs a program cycle by cycle, iterating over `(cycle, x)` during each cycle
+pub struct Cpu<'a, I: Execute = Instruction> {
+	program: &'a [I],
+	/// Index of the instruction in progress
+	pc: usize,
+	/// Cycles already spent on the instruction in progress
+	elapsed: u32,
+	/// The next cycle to run, starting from 1
+	cycle: u32,
+	registers: Registers,
+	breakpoints: HashSet<u32>,
+	watches: Vec<Register>,
+	/// A breakpoint hit on the same cycle as a watch, reported by the next run
+	pending: Option<Stop>,
+}
+
+impl<'a, I: Execute> Cpu<'a, I> {
+	pub fn new(program: &'a [I]) -> Self {
+		Cpu {
+			program,
+			pc: 0,
+			elapsed: 0,
+			cycle: 1,
+			registers: Registers::default(),
+			breakpoints: HashSet::new(),
+			watches: vec![],
+			pending: None,
+		}
+	}
+
+	pub fn next_cycle(&self) -> u32 {
+		self.cycle
+	}
+
+	pub fn registers(&self) -> Registers {
+		self.registers
+	}
+
+	pub fn x(&self) -> i32 {
+		self.registers.x
+	}
+
+	pub fn halted(&self) -> bool {
+		self.pc >= self.program.len()
+	}
+
+	pub fn add_breakpoint(&mut self, cycle: u32) {
+		self.breakpoints.insert(cycle);
+	}
+
+	pub fn remove_breakpoint(&mut self, cycle: u32) -> bool {
+		self.breakpoints.remove(&cycle)
+	}
+
+	pub fn watch(&mut self, register: Register) {
+		if !self.watches.contains(&register) {
+			self.watches.push(register);
+		}
+	}
+
+	pub fn unwatch(&mut self, register: Register) {
+		self.watches.retain(|watched| *watched != register);
+	}
+
+	/// Runs a single cycle, returning the cycle and the value of X during it
+	pub fn step(&mut self) -> Option<(u32, i32)> {
+		let instruction = self.program.get(self.pc)?;
+		let during = (self.cycle, self.registers.x);
+
+		self.elapsed += 1;
+		if self.elapsed >= instruction.cycles() {
+			instruction.execute(&mut self.registers);
+			self.pc += 1;
+			self.elapsed = 0;
+		}
+		self.cycle += 1;
+		Some(during)
+	}
+
+	/// Runs until halted, a breakpoint or a watched register changes, calling `during_cycle` for each cycle.
+	/// When a watch and a breakpoint fire on the same cycle the watch is returned first,
+	/// and the next run returns the breakpoint without running anything.
+	pub fn run(&mut self, mut during_cycle: impl FnMut(u32, i32)) -> Stop {
+		if let Some(stop) = self.pending.take() {
+			return stop;
+		}
+		loop {
+			let before = self.registers;
+			let Some((cycle, x)) = self.step() else {
+				return Stop::Halted;
+			};
+			during_cycle(cycle, x);
+
+			let breakpoint = self
+				.breakpoints
+				.contains(&cycle)
+				.then_some(Stop::Breakpoint(cycle));
+			if let Some(register) = self
+				.watches
+				.iter()
+				.find(|register| before.get(**register) != self.registers.get(**register))
+			{
+				self.pending = breakpoint;
+				return Stop::Watch {
+					cycle,
+					register: *register,
+					old: before.get(*register),
+					new: self.registers.get(*register),
+				};
+			}
+			if let Some(stop) = breakpoint {
+				return stop;
+			}
+		}
+	}
 }
 
-fn boost_signal(signal: i32, cycle: i32, x: i32) -> i32 {
-	match cycle % 40 {
-		20 => signal + cycle * x,
-		_ => signal,
+impl<I: Execute> Iterator for Cpu<'_, I> {
+	type Item = (u32, i32);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.step()
 	}
 }
 
@@ -92,6 +325,21 @@ pub enum Instruction {
 	Addx(i32),
 }
 
+impl Execute for Instruction {
+	fn cycles(&self) -> u32 {
+		match self {
+			Instruction::Noop => 1,
+			Instruction::Addx(_) => 2,
+		}
+	}
+
+	fn execute(&self, registers: &mut Registers) {
+		if let Instruction::Addx(v) = self {
+			registers.x += v;
+		}
+	}
+}
+
 fn parse_instructions(input: &str) -> IResult<&str, Vec<Instruction>> {
 	separated_list1(newline, parse_instruction)(input)
 }
@@ -110,11 +358,190 @@ fn parse_addx(input: &str) -> IResult<&str, Instruction> {
 	Ok((input, Instruction::Addx(value)))
 }
 
+/// Recognition of the 4x6 capital letters drawn by AoC puzzles
+pub mod ocr {
+	use itertools::Itertools;
+	use std::cmp::Reverse;
+	use std::fmt;
+
+	pub const GLYPH_WIDTH: usize = 4;
+	pub const GLYPH_HEIGHT: usize = 6;
+	/// Glyphs are separated by a blank column
+	pub const GLYPH_SPACING: usize = GLYPH_WIDTH + 1;
+
+	/// Every letter known to appear in puzzle output
+	pub const FONT: &[(char, [&str; GLYPH_HEIGHT])] = &[
+		('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+		('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+		('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+		('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+		('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+		('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+		('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+		('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+		('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+		('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+		('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+		('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+		('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+		('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+		('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+		('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+		('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+	];
+
+	#[derive(Debug, PartialEq, Eq, Clone)]
+	pub enum OcrError {
+		WrongHeight(usize),
+		/// A line isn't as long as most of the others, which are `expected` long
+		RaggedLine {
+			line: usize,
+			len: usize,
+			expected: usize,
+		},
+		UnknownGlyph {
+			index: usize,
+			glyph: Vec<String>,
+		},
+	}
+
+	impl fmt::Display for OcrError {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			match self {
+				OcrError::WrongHeight(height) => {
+					write!(f, "image is {height} lines high instead of {GLYPH_HEIGHT}")
+				}
+				OcrError::RaggedLine {
+					line,
+					len,
+					expected,
+				} => write!(
+					f,
+					"line {line} is {len} long where most lines are {expected}"
+				),
+				OcrError::UnknownGlyph { index, glyph } => {
+					write!(f, "unknown glyph {index}:\n{}", glyph.join("\n"))
+				}
+			}
+		}
+	}
+
+	/// Decoded text along with the image it was read from
+	#[derive(Debug, PartialEq, Eq, Clone)]
+	pub struct Reading {
+		pub text: String,
+		pub image: String,
+	}
+
+	impl fmt::Display for Reading {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			write!(f, "{}\n{}", self.text, self.image)
+		}
+	}
+
+	/// Reads the text from an image of `#` and `.`, failing on anything not in the [`FONT`]
+	pub fn read(image: &str) -> Result<String, OcrError> {
+		let lines: Vec<&str> = image.lines().collect();
+		if lines.len() != GLYPH_HEIGHT {
+			return Err(OcrError::WrongHeight(lines.len()));
+		}
+		let widths: Vec<usize> = lines.iter().map(|line| line.chars().count()).collect();
+		// Measured against the width most lines agree on, so the odd one out is the one reported
+		let (width, _) = widths
+			.iter()
+			.copied()
+			.counts()
+			.into_iter()
+			.max_by_key(|(width, count)| (*count, Reverse(*width)))
+			.unwrap();
+		if let Some((line, len)) = widths
+			.iter()
+			.copied()
+			.enumerate()
+			.find(|(_, len)| *len != width)
+		{
+			return Err(OcrError::RaggedLine {
+				line,
+				len,
+				expected: width,
+			});
+		}
+
+		let rows: Vec<Vec<char>> = lines.iter().map(|line| line.chars().collect()).collect();
+		(0..width.div_ceil(GLYPH_SPACING))
+			.map(|index| {
+				let start = index * GLYPH_SPACING;
+				let end = width.min(start + GLYPH_SPACING);
+				// A lit separator means the glyph is wider than the font, so it can't be trusted
+				if rows
+					.iter()
+					.any(|row| row[start..end].get(GLYPH_WIDTH) == Some(&'#'))
+				{
+					let glyph = rows
+						.iter()
+						.map(|row| row[start..end].iter().collect())
+						.collect();
+					return Err(OcrError::UnknownGlyph { index, glyph });
+				}
+				let glyph: Vec<String> = rows
+					.iter()
+					.map(|row| row[start..width.min(start + GLYPH_WIDTH)].iter().collect())
+					.collect();
+				recognise(&glyph).ok_or(OcrError::UnknownGlyph { index, glyph })
+			})
+			.collect()
+	}
+
+	pub fn read_keeping_image(image: &str) -> Result<Reading, OcrError> {
+		Ok(Reading {
+			text: read(image)?,
+			image: image.to_string(),
+		})
+	}
+
+	/// The letter drawn by exactly [`GLYPH_HEIGHT`] rows of [`GLYPH_WIDTH`] pixels
+	pub fn recognise<S: AsRef<str>>(glyph: &[S]) -> Option<char> {
+		if glyph.len() != GLYPH_HEIGHT
+			|| glyph
+				.iter()
+				.any(|row| row.as_ref().chars().count() != GLYPH_WIDTH)
+		{
+			return None;
+		}
+		FONT.iter()
+			.find(|(_, rows)| rows.iter().zip(glyph).all(|(a, b)| *a == b.as_ref()))
+			.map(|(c, _)| *c)
+	}
+
+	/// Draws `text` in the [`FONT`], `None` if it has a letter that isn't known
+	pub fn draw(text: &str) -> Option<String> {
+		let glyphs = text
+			.chars()
+			.map(|c| {
+				FONT.iter()
+					.find(|(letter, _)| *letter == c)
+					.map(|(_, rows)| rows)
+			})
+			.collect::<Option<Vec<_>>>()?;
+		Some(
+			(0..GLYPH_HEIGHT)
+				.map(|row| {
+					glyphs
+						.iter()
+						.map(|glyph| format!("{}.", glyph[row]))
+						.collect::<String>()
+				})
+				.collect::<Vec<String>>()
+				.join("\n"),
+		)
+	}
+}
+
 #[cfg(test)]
 mod test {
 	use crate::day10::{
-		gen, parse_addx, parse_instruction, parse_instructions, parse_noop, solve_part1,
-		solve_part2, Instruction,
+		gen, ocr, parse_addx, parse_instruction, parse_instructions, parse_noop, solve_part1,
+		solve_part2, solve_part2_ocr, Cpu, Crt, Execute, Instruction, Register, Registers, Stop,
 	};
 
 	const SMALL_EXAMPLE: &str = r"noop
@@ -144,7 +571,7 @@ addx -5";
 	fn instructions() {
 		let (_, instructions) = parse_instructions(SMALL_EXAMPLE).unwrap();
 
-		let expected_instructions = vec![
+		let expected_instructions = [
 			Instruction::Noop,
 			Instruction::Addx(3),
 			Instruction::Addx(-5),
@@ -156,6 +583,123 @@ addx -5";
 			.for_each(|(instruction, expected)| assert_eq!(instruction, expected));
 	}
 
+	#[test]
+	fn cycles() {
+		let program = gen(SMALL_EXAMPLE);
+		let mut cpu = Cpu::new(&program);
+		assert_eq!(
+			vec![(1, 1), (2, 1), (3, 1), (4, 4), (5, 4)],
+			cpu.by_ref().collect::<Vec<_>>()
+		);
+		assert!(cpu.halted());
+		assert_eq!(-1, cpu.x());
+		assert_eq!(6, cpu.next_cycle());
+	}
+
+	#[test]
+	fn breakpoints_and_watches() {
+		let program = gen(LARGE_EXAMPLE);
+		let mut cpu = Cpu::new(&program);
+		cpu.add_breakpoint(20);
+		cpu.add_breakpoint(60);
+
+		let mut seen = vec![];
+		assert_eq!(
+			Stop::Breakpoint(20),
+			cpu.run(|cycle, x| seen.push((cycle, x)))
+		);
+		assert_eq!(Some(&(20, 21)), seen.last());
+		assert_eq!(Stop::Breakpoint(60), cpu.run(|_, _| {}));
+		assert!(cpu.remove_breakpoint(60));
+
+		cpu.watch(Register::X);
+		assert_eq!(
+			Stop::Watch {
+				cycle: 61,
+				register: Register::X,
+				old: 19,
+				new: 16
+			},
+			cpu.run(|_, _| {})
+		);
+
+		cpu.unwatch(Register::X);
+		assert_eq!(Stop::Halted, cpu.run(|_, _| {}));
+		assert_eq!(241, cpu.next_cycle());
+
+		// Both stops are reported when they land on the same cycle
+		let mut cpu = Cpu::new(&program);
+		cpu.add_breakpoint(61);
+		cpu.add_breakpoint(62);
+		cpu.watch(Register::X);
+		let mut cycles = 0;
+		let watch = loop {
+			match cpu.run(|_, _| cycles += 1) {
+				stop @ Stop::Watch { cycle: 61, .. } => break stop,
+				Stop::Halted => panic!("never reached cycle 61"),
+				_ => {}
+			}
+		};
+		assert_eq!(
+			Stop::Watch {
+				cycle: 61,
+				register: Register::X,
+				old: 19,
+				new: 16
+			},
+			watch
+		);
+		assert_eq!(61, cycles);
+		assert_eq!(Stop::Breakpoint(61), cpu.run(|_, _| cycles += 1));
+		assert_eq!(61, cycles);
+		assert_eq!(Stop::Breakpoint(62), cpu.run(|_, _| {}));
+	}
+
+	/// An instruction set extended with a slow multiply
+	enum Extended {
+		Base(Instruction),
+		Mulx(i32),
+	}
+
+	impl Execute for Extended {
+		fn cycles(&self) -> u32 {
+			match self {
+				Extended::Base(instruction) => instruction.cycles(),
+				Extended::Mulx(_) => 3,
+			}
+		}
+
+		fn execute(&self, registers: &mut Registers) {
+			match self {
+				Extended::Base(instruction) => instruction.execute(registers),
+				Extended::Mulx(v) => registers.x *= v,
+			}
+		}
+	}
+
+	#[test]
+	fn extended_instructions() {
+		let program = vec![
+			Extended::Base(Instruction::Addx(2)),
+			Extended::Mulx(4),
+			Extended::Base(Instruction::Noop),
+		];
+		assert_eq!(
+			vec![(1, 1), (2, 1), (3, 3), (4, 3), (5, 3), (6, 12)],
+			Cpu::new(&program).collect::<Vec<_>>()
+		);
+		// The Crt can be driven by any instruction set
+		let crt = Crt {
+			width: 6,
+			height: 1,
+			sprite_width: 3,
+			first_sample: 2,
+			sample_every: 2,
+		};
+		assert_eq!("#####.", crt.render(&program));
+		assert_eq!(2 + 4 * 3 + 6 * 12, crt.signal_strength(&program));
+	}
+
 	#[test]
 	fn part1() {
 		assert_eq!(0, solve_part1(&gen(SMALL_EXAMPLE)));
@@ -175,6 +719,150 @@ addx -5";
 		assert_eq!(expected, solve_part2(&gen(LARGE_EXAMPLE)));
 	}
 
+	#[test]
+	fn part2_ocr() {
+		// The example draws stripes rather than letters
+		assert!(matches!(
+			solve_part2_ocr(&gen(LARGE_EXAMPLE)),
+			Err(ocr::OcrError::UnknownGlyph { index: 0, .. })
+		));
+	}
+
+	#[test]
+	fn crt_geometry() {
+		let program = gen(LARGE_EXAMPLE);
+		let crt = Crt {
+			width: 20,
+			height: 3,
+			sprite_width: 1,
+			first_sample: 10,
+			sample_every: 0,
+		};
+		assert_eq!(80, crt.signal_strength(&program));
+		assert_eq!(
+			".#...#..#...#....#..\n....................\n..#.....#....#.....#",
+			crt.render(&program)
+		);
+
+		let wide = Crt {
+			sprite_width: 5,
+			..Crt::default()
+		};
+		assert!(wide.lit(3, 5) && wide.lit(7, 5) && !wide.lit(8, 5));
+		assert_eq!(
+			Crt::default().signal_strength(&program),
+			solve_part1(&program)
+		);
+
+		// A short program leaves the rest of the screen dark
+		let screen = Crt::default().screen(&gen(SMALL_EXAMPLE));
+		assert_eq!(6, screen.len());
+		assert_eq!(
+			vec![true, true, true, true, true, false],
+			screen[0][..6].to_vec()
+		);
+		assert!(screen[1].iter().all(|lit| !lit));
+	}
+
+	#[test]
+	fn image_export() {
+		let program = gen(SMALL_EXAMPLE);
+		let crt = Crt {
+			width: 6,
+			height: 1,
+			..Crt::default()
+		};
+		assert_eq!("P1\n6 1\n1 1 1 1 1 0\n", crt.pbm(&program));
+		// A full width row wraps to stay within the plain PBM line limit
+		let pbm = Crt::default().pbm(&program);
+		assert_eq!(2 + 6 * 2, pbm.lines().count());
+		assert!(pbm.lines().all(|line| line.len() <= 70));
+
+		let png = crt.png(&program, 2).unwrap();
+		let mut reader = png::Decoder::new(&png[..]).read_info().unwrap();
+		let mut pixels = vec![0; reader.output_buffer_size()];
+		let frame = reader.next_frame(&mut pixels).unwrap();
+		assert_eq!((12, 2), (frame.width, frame.height));
+		assert_eq!(png::ColorType::Grayscale, frame.color_type);
+		let lit = [
+			0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0,
+		];
+		assert_eq!([lit, lit].concat(), pixels);
+	}
+
+	#[test]
+	fn reading_text() {
+		let image = ocr::draw("EHZFZHCZ").unwrap();
+		assert_eq!(
+			"####.#..#.####.####.####.#..#..##..####.",
+			image.lines().next().unwrap()
+		);
+		assert_eq!(Ok("EHZFZHCZ".to_string()), ocr::read(&image));
+
+		// The trailing blank column is optional
+		let trimmed: Vec<&str> = image.lines().map(|line| &line[..39]).collect();
+		assert_eq!(Ok("EHZFZHCZ".to_string()), ocr::read(&trimmed.join("\n")));
+
+		let reading = ocr::read_keeping_image(&image).unwrap();
+		assert_eq!(image, reading.image);
+		assert_eq!(None, ocr::draw("EHQ"));
+
+		// Only a whole glyph is recognised
+		let a = [".##.", "#..#", "#..#", "####", "#..#", "#..#"];
+		assert_eq!(Some('A'), ocr::recognise(&a));
+		assert_eq!(None, ocr::recognise(&a[..5]));
+		assert_eq!(None, ocr::recognise::<&str>(&[]));
+		assert_eq!(None, ocr::recognise(&a.map(|row| &row[..3])));
+	}
+
+	#[test]
+	fn unreadable_images() {
+		assert_eq!(Err(ocr::OcrError::WrongHeight(1)), ocr::read("####"));
+
+		// The first line is the one that's too long
+		let ragged = ocr::draw("AB").unwrap().replacen('\n', ".\n", 1);
+		assert_eq!(
+			Err(ocr::OcrError::RaggedLine {
+				line: 0,
+				len: 11,
+				expected: 10
+			}),
+			ocr::read(&ragged)
+		);
+		let short = ocr::draw("AB").unwrap().replacen(".\n", "\n", 1);
+		assert_eq!(
+			Err(ocr::OcrError::RaggedLine {
+				line: 0,
+				len: 9,
+				expected: 10
+			}),
+			ocr::read(&short)
+		);
+
+		let smudged = ocr::draw("AB").unwrap().replacen('#', ".", 1);
+		assert_eq!(
+			Err(ocr::OcrError::UnknownGlyph {
+				index: 0,
+				glyph: ["..#.", "#..#", "#..#", "####", "#..#", "#..#"]
+					.map(String::from)
+					.to_vec()
+			}),
+			ocr::read(&smudged)
+		);
+		// A lit separator column isn't just ignored
+		let mut bled = ocr::draw("AB").unwrap();
+		bled.replace_range(4..5, "#");
+		assert_eq!(
+			Err(ocr::OcrError::UnknownGlyph {
+				index: 0,
+				glyph: [".##.#", "#..#.", "#..#.", "####.", "#..#.", "#..#."]
+					.map(String::from)
+					.to_vec()
+			}),
+			ocr::read(&bled)
+		);
+	}
+
 	const LARGE_EXAMPLE: &str = r"addx 15
 addx -11
 addx 6
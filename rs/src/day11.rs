@@ -1,10 +1,16 @@
 use itertools::Itertools;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{char as nom_char, line_ending, newline, one_of, u64 as nom_u64};
-use nom::multi::{count, separated_list1};
-use nom::sequence::{preceded, separated_pair, terminated};
+use nom::character::complete::{
+	char as nom_char, line_ending, newline, one_of, space0, u64 as nom_u64,
+};
+use nom::combinator::verify;
+use nom::multi::{count, fold_many0, separated_list1};
+use nom::sequence::{delimited, pair, preceded, terminated};
 use nom::IResult;
+use num_bigint::BigUint;
+use std::fmt;
+use std::iter::once;
 use yaah::*;
 
 #[aoc_generator(day11)]
@@ -13,53 +19,379 @@ fn gen(input: &'static str) -> Vec<Monkey> {
 }
 
 #[aoc(day11, part1)]
-fn solve_part1(monkeys: &Vec<Monkey>) -> u64 {
-	monkey_business(monkeys, 20, true)
+fn solve_part1(monkeys: &[Monkey]) -> Result<u64, WorryError> {
+	let relief = Expression::binary(Operator::Divide, Expression::Old, Expression::Value(3));
+	monkey_business(monkeys, 20, &relief, Arithmetic::Checked)
 }
 
 #[aoc(day11, part2)]
-fn solve_part2(monkeys: &Vec<Monkey>) -> u64 {
-	monkey_business(monkeys, 10000, false)
+fn solve_part2(monkeys: &[Monkey]) -> Result<u64, WorryError> {
+	monkey_business(monkeys, 10000, &Expression::Old, Arithmetic::Modular)
 }
 
-fn monkey_business(monkeys: &Vec<Monkey>, rounds: usize, div3: bool) -> u64 {
-	let mut items: Vec<Vec<u64>> = monkeys
-		.iter()
-		.map(|monkey| monkey.starting_items.clone())
-		.collect();
+/// How worry levels are represented during the simulation
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Arithmetic {
+	/// Modulo the product of every test, only sound without division or subtraction
+	Modular,
+	/// `u64` failing on overflow
+	Checked,
+	/// Arbitrary precision, exact but slow once worry levels grow
+	Exact,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WorryError {
+	/// An operation or the relief divides or subtracts, so working modulo the tests changes the outcome
+	NotCongruent,
+	/// The product of every test is too large to work modulo
+	ModulusOverflow,
+	/// A worry level overflowed, went negative or was divided by zero
+	Overflow { round: usize, monkey: usize },
+}
+
+impl fmt::Display for WorryError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			WorryError::NotCongruent => write!(
+				f,
+				"worry levels can't be reduced when dividing or subtracting"
+			),
+			WorryError::ModulusOverflow => {
+				write!(f, "the product of the tests doesn't fit in a worry level")
+			}
+			WorryError::Overflow { round, monkey } => write!(
+				f,
+				"worry level out of range when monkey {monkey} inspected an item in round {round}"
+			),
+		}
+	}
+}
+
+/// Product of the two highest inspection counts, `relief` being applied to `old` after each inspection
+pub fn monkey_business(
+	monkeys: &[Monkey],
+	rounds: usize,
+	relief: &Expression,
+	arithmetic: Arithmetic,
+) -> Result<u64, WorryError> {
+	run(monkeys, rounds, relief, arithmetic, None).map(|inspections| top_two_product(&inspections))
+}
 
-	let common_product: u64 = monkeys
-		.iter()
-		.map(|monkey| monkey.test.divisible_by)
-		.product::<u64>();
+/// Runs the simulation like [`monkey_business`], recording where every item went
+pub fn trace(
+	monkeys: &[Monkey],
+	rounds: usize,
+	relief: &Expression,
+	arithmetic: Arithmetic,
+) -> Result<Trace, WorryError> {
+	let mut trace = Trace::default();
+	run(monkeys, rounds, relief, arithmetic, Some(&mut trace))?;
+	Ok(trace)
+}
+
+fn top_two_product(inspections: &[u64]) -> u64 {
+	inspections.iter().sorted().rev().take(2).product()
+}
+
+fn run(
+	monkeys: &[Monkey],
+	rounds: usize,
+	relief: &Expression,
+	arithmetic: Arithmetic,
+	trace: Option<&mut Trace>,
+) -> Result<Vec<u64>, WorryError> {
+	match arithmetic {
+		Arithmetic::Modular => {
+			if !relief.preserves_congruence()
+				|| !monkeys
+					.iter()
+					.all(|monkey| monkey.operation.preserves_congruence())
+			{
+				return Err(WorryError::NotCongruent);
+			}
+			let common_product = monkeys
+				.iter()
+				.try_fold(1u64, |product, monkey| {
+					product.checked_mul(monkey.test.divisible_by)
+				})
+				.ok_or(WorryError::ModulusOverflow)?;
+			simulate(
+				monkeys,
+				rounds,
+				relief,
+				|item| Residue::new(item, common_product),
+				trace,
+			)
+		}
+		Arithmetic::Checked => simulate(monkeys, rounds, relief, |item| item, trace),
+		Arithmetic::Exact => simulate(monkeys, rounds, relief, BigUint::from, trace),
+	}
+}
+
+/// Inspection counts of every monkey, items being numbered in input order when tracing
+fn simulate<W: Worry>(
+	monkeys: &[Monkey],
+	rounds: usize,
+	relief: &Expression,
+	lift: impl Fn(u64) -> W,
+	mut trace: Option<&mut Trace>,
+) -> Result<Vec<u64>, WorryError> {
+	let mut items: Vec<Vec<(usize, W)>> = vec![];
+	let mut next_item = 0;
+	for monkey in monkeys {
+		let mut held = vec![];
+		for item in &monkey.starting_items {
+			let worry_level = lift(*item);
+			if let Some(trace) = trace.as_deref_mut() {
+				trace.items.push(vec![Visit {
+					round: 0,
+					monkey: monkey.number,
+					worry_level: worry_level.to_biguint(),
+				}]);
+			}
+			held.push((next_item, worry_level));
+			next_item += 1;
+		}
+		items.push(held);
+	}
 
 	let mut inspections: Vec<u64> = monkeys.iter().map(|_| 0).collect();
-	for _ in 0..rounds {
+	for round in 1..=rounds {
+		let mut round_inspections: Vec<u64> = monkeys.iter().map(|_| 0).collect();
 		for monkey in monkeys {
-			for item in items[monkey.number].clone() {
-				inspections[monkey.number] += 1;
-				let other_value = match monkey.operation.operand {
-					Operand::Old => item,
-					Operand::Value(value) => value,
-				};
-				let worry_level: u64 = match monkey.operation.operator {
-					Operator::Plus => item + other_value,
-					Operator::Times => item * other_value,
-				};
-				let worry_level = match div3 {
-					true => worry_level / 3,
-					false => worry_level % common_product,
-				};
-				let thrown_to = match worry_level % monkey.test.divisible_by == 0 {
+			for (id, item) in std::mem::take(&mut items[monkey.number]) {
+				round_inspections[monkey.number] += 1;
+				let worry_level = monkey
+					.operation
+					.evaluate_with(&item)
+					.and_then(|worry_level| relief.evaluate_with(&worry_level))
+					.ok_or(WorryError::Overflow {
+						round,
+						monkey: monkey.number,
+					})?;
+				let thrown_to = match worry_level.is_divisible_by(monkey.test.divisible_by) {
 					true => monkey.test.if_true as usize,
 					false => monkey.test.if_false as usize,
 				};
-				items[thrown_to].push(worry_level);
+				if let Some(trace) = trace.as_deref_mut() {
+					trace.items[id].push(Visit {
+						round,
+						monkey: thrown_to,
+						worry_level: worry_level.to_biguint(),
+					});
+				}
+				items[thrown_to].push((id, worry_level));
+			}
+		}
+		inspections
+			.iter_mut()
+			.zip(&round_inspections)
+			.for_each(|(total, count)| *total += count);
+		if let Some(trace) = trace.as_deref_mut() {
+			trace.rounds.push(round_inspections);
+		}
+	}
+	Ok(inspections)
+}
+
+/// An item arriving at a monkey, the starting items arriving in round 0
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Visit {
+	pub round: usize,
+	pub monkey: usize,
+	/// Worry level as computed, which is only a residue in modular arithmetic
+	pub worry_level: BigUint,
+}
+
+/// Where every item went and how busy the monkeys were each round
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Trace {
+	/// Visits of each item, numbered in the order they're listed in the input
+	pub items: Vec<Vec<Visit>>,
+	/// Inspections by each monkey, the first round at index 0
+	pub rounds: Vec<Vec<u64>>,
+}
+
+impl Trace {
+	/// Monkeys that held `item`, in order
+	pub fn path(&self, item: usize) -> Option<Vec<usize>> {
+		self.items
+			.get(item)
+			.map(|visits| visits.iter().map(|visit| visit.monkey).collect())
+	}
+
+	/// Inspections by each monkey during `round`, counted from 1
+	pub fn round(&self, round: usize) -> Option<&[u64]> {
+		self.rounds
+			.get(round.checked_sub(1)?)
+			.map(|counts| &counts[..])
+	}
+
+	/// Inspections by each monkey over every round
+	pub fn inspections(&self) -> Vec<u64> {
+		self.rounds.iter().fold(vec![], |mut totals, counts| {
+			totals.resize(counts.len(), 0);
+			totals
+				.iter_mut()
+				.zip(counts)
+				.for_each(|(total, count)| *total += count);
+			totals
+		})
+	}
+
+	pub fn monkey_business(&self) -> u64 {
+		top_two_product(&self.inspections())
+	}
+
+	/// Items `monkey` received, with the visit
+	pub fn visits_to(&self, monkey: usize) -> impl Iterator<Item = (usize, &Visit)> {
+		self.items
+			.iter()
+			.enumerate()
+			.flat_map(move |(item, visits)| {
+				visits
+					.iter()
+					.filter(move |visit| visit.monkey == monkey)
+					.map(move |visit| (item, visit))
+			})
+	}
+
+	/// One row per visit, `item,step,round,monkey,worry_level`
+	pub fn items_csv(&self) -> String {
+		let mut csv = "item,step,round,monkey,worry_level\n".to_string();
+		for (item, visits) in self.items.iter().enumerate() {
+			for (step, visit) in visits.iter().enumerate() {
+				csv.push_str(&format!(
+					"{item},{step},{},{},{}\n",
+					visit.round, visit.monkey, visit.worry_level
+				));
 			}
-			items[monkey.number].clear();
 		}
+		csv
+	}
+
+	/// One row per round with a column of inspections for each monkey
+	pub fn rounds_csv(&self) -> String {
+		let monkeys = self.rounds.first().map_or(0, |counts| counts.len());
+		let mut csv = once("round".to_string())
+			.chain((0..monkeys).map(|monkey| format!("monkey{monkey}")))
+			.join(",");
+		csv.push('\n');
+		for (round, counts) in self.rounds.iter().enumerate() {
+			csv.push_str(&format!("{},{}\n", round + 1, counts.iter().join(",")));
+		}
+		csv
+	}
+}
+
+/// Numbers worry levels can be computed with, `None` when a result can't be represented
+pub trait Worry: Clone {
+	/// `value` in the same representation as `self`
+	fn constant(&self, value: u64) -> Self;
+	fn apply(&self, operator: Operator, other: &Self) -> Option<Self>;
+	fn is_divisible_by(&self, divisor: u64) -> bool;
+	fn to_biguint(&self) -> BigUint;
+}
+
+impl Worry for u64 {
+	fn constant(&self, value: u64) -> Self {
+		value
+	}
+
+	fn apply(&self, operator: Operator, other: &Self) -> Option<Self> {
+		match operator {
+			Operator::Plus => self.checked_add(*other),
+			Operator::Minus => self.checked_sub(*other),
+			Operator::Times => self.checked_mul(*other),
+			Operator::Divide => self.checked_div(*other),
+		}
+	}
+
+	fn is_divisible_by(&self, divisor: u64) -> bool {
+		self.is_multiple_of(divisor)
+	}
+
+	fn to_biguint(&self) -> BigUint {
+		BigUint::from(*self)
+	}
+}
+
+/// A worry level modulo a multiple of every divisor it gets tested against
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Residue {
+	value: u64,
+	modulus: u64,
+}
+
+impl Residue {
+	/// # Panics
+	/// If `modulus` is zero
+	pub fn new(value: u64, modulus: u64) -> Self {
+		Residue {
+			value: value % modulus,
+			modulus,
+		}
+	}
+
+	pub fn value(&self) -> u64 {
+		self.value
+	}
+}
+
+impl Worry for Residue {
+	fn constant(&self, value: u64) -> Self {
+		Residue::new(value, self.modulus)
+	}
+
+	fn apply(&self, operator: Operator, other: &Self) -> Option<Self> {
+		let (left, right, modulus) = (
+			self.value as u128,
+			other.value as u128,
+			self.modulus as u128,
+		);
+		let value = match operator {
+			Operator::Plus => left + right,
+			Operator::Times => left * right,
+			// A residue can't tell whether the real value would have gone negative
+			Operator::Minus | Operator::Divide => return None,
+		};
+		Some(Residue {
+			value: (value % modulus) as u64,
+			modulus: self.modulus,
+		})
+	}
+
+	fn is_divisible_by(&self, divisor: u64) -> bool {
+		self.value.is_multiple_of(divisor)
+	}
+
+	fn to_biguint(&self) -> BigUint {
+		BigUint::from(self.value)
+	}
+}
+
+impl Worry for BigUint {
+	fn constant(&self, value: u64) -> Self {
+		BigUint::from(value)
+	}
+
+	fn apply(&self, operator: Operator, other: &Self) -> Option<Self> {
+		match operator {
+			Operator::Plus => Some(self + other),
+			Operator::Minus => (self >= other).then(|| self - other),
+			Operator::Times => Some(self * other),
+			Operator::Divide => (other.bits() > 0).then(|| self / other),
+		}
+	}
+
+	fn is_divisible_by(&self, divisor: u64) -> bool {
+		(self % divisor).bits() == 0
+	}
+
+	fn to_biguint(&self) -> BigUint {
+		self.clone()
 	}
-	inspections.into_iter().sorted().rev().take(2).product()
 }
 
 fn starting_items(input: &str) -> IResult<&str, Vec<u64>> {
@@ -69,44 +401,62 @@ fn starting_items(input: &str) -> IResult<&str, Vec<u64>> {
 	)(input)
 }
 
-fn operator(input: &str) -> IResult<&str, Operator> {
-	let (input, op) = one_of("+*")(input)?;
-	Ok((
-		input,
-		match op {
-			'*' => Operator::Times,
-			_ => Operator::Plus,
-		},
-	))
+fn operation(input: &str) -> IResult<&str, Expression> {
+	preceded(tag("  Operation: new = "), expression)(input)
 }
 
-fn operand(input: &str) -> IResult<&str, Operand> {
-	alt((parse_old, operand_value))(input)
+/// Sums and differences of terms, left associative
+fn expression(input: &str) -> IResult<&str, Expression> {
+	let (input, first) = term(input)?;
+	fold_many0(
+		pair(delimited(space0, one_of("+-"), space0), term),
+		move || first.clone(),
+		|left, (op, right)| match op {
+			'+' => Expression::binary(Operator::Plus, left, right),
+			_ => Expression::binary(Operator::Minus, left, right),
+		},
+	)(input)
 }
 
-fn parse_old(input: &str) -> IResult<&str, Operand> {
-	let (input, _) = tag("old")(input)?;
-	Ok((input, Operand::Old))
+/// Products and quotients of factors, left associative
+fn term(input: &str) -> IResult<&str, Expression> {
+	let (input, first) = factor(input)?;
+	fold_many0(
+		pair(delimited(space0, one_of("*/"), space0), factor),
+		move || first.clone(),
+		|left, (op, right)| match op {
+			'*' => Expression::binary(Operator::Times, left, right),
+			_ => Expression::binary(Operator::Divide, left, right),
+		},
+	)(input)
 }
 
-fn operand_value(input: &str) -> IResult<&str, Operand> {
-	let (input, value) = nom_u64(input)?;
-	Ok((input, Operand::Value(value)))
+fn factor(input: &str) -> IResult<&str, Expression> {
+	alt((
+		parse_old,
+		value,
+		delimited(
+			terminated(nom_char('('), space0),
+			expression,
+			preceded(space0, nom_char(')')),
+		),
+	))(input)
 }
 
-fn operation(input: &str) -> IResult<&str, Operation> {
-	let (input, (operator, operand)) = preceded(
-		tag("  Operation: new = old "),
-		separated_pair(operator, nom_char(' '), operand),
-	)(input)?;
+fn parse_old(input: &str) -> IResult<&str, Expression> {
+	let (input, _) = tag("old")(input)?;
+	Ok((input, Expression::Old))
+}
 
-	Ok((input, Operation { operator, operand }))
+fn value(input: &str) -> IResult<&str, Expression> {
+	let (input, value) = nom_u64(input)?;
+	Ok((input, Expression::Value(value)))
 }
 
 fn test_expression(input: &str) -> IResult<&str, TestExpression> {
 	let (input, divisible_by) = preceded(
 		tag("  Test: divisible by "),
-		terminated(nom_u64, line_ending),
+		terminated(verify(nom_u64, |divisor| *divisor > 0), line_ending),
 	)(input)?;
 	let (input, if_true) = preceded(
 		tag("    If true: throw to monkey "),
@@ -151,19 +501,51 @@ fn monkeys(input: &str) -> IResult<&str, Vec<Monkey>> {
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Operator {
 	Plus,
+	Minus,
 	Times,
+	Divide,
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub enum Operand {
+/// Right hand side of a monkey's `new = ...` operation
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
 	Old,
 	Value(u64),
+	Binary(Operator, Box<Expression>, Box<Expression>),
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-struct Operation {
-	operator: Operator,
-	operand: Operand,
+impl Expression {
+	pub fn binary(operator: Operator, left: Expression, right: Expression) -> Self {
+		Expression::Binary(operator, Box::new(left), Box::new(right))
+	}
+
+	/// Evaluates with `u64`, `None` on overflow, underflow or division by zero
+	pub fn evaluate(&self, old: u64) -> Option<u64> {
+		self.evaluate_with(&old)
+	}
+
+	pub fn evaluate_with<W: Worry>(&self, old: &W) -> Option<W> {
+		match self {
+			Expression::Old => Some(old.clone()),
+			Expression::Value(value) => Some(old.constant(*value)),
+			Expression::Binary(operator, left, right) => {
+				let left = left.evaluate_with(old)?;
+				left.apply(*operator, &right.evaluate_with(old)?)
+			}
+		}
+	}
+
+	/// Whether working modulo `m` gives the same result modulo `m`. Division doesn't, and
+	/// subtraction would hide worry levels going negative, which the other arithmetic rejects.
+	pub fn preserves_congruence(&self) -> bool {
+		match self {
+			Expression::Old | Expression::Value(_) => true,
+			Expression::Binary(Operator::Divide | Operator::Minus, _, _) => false,
+			Expression::Binary(_, left, right) => {
+				left.preserves_congruence() && right.preserves_congruence()
+			}
+		}
+	}
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -177,15 +559,16 @@ struct TestExpression {
 pub struct Monkey {
 	number: usize,
 	starting_items: Vec<u64>,
-	operation: Operation,
+	operation: Expression,
 	test: TestExpression,
 }
 
 #[cfg(test)]
 mod test {
 	use crate::day11::{
-		gen, monkey, monkeys, operation, solve_part1, solve_part2, starting_items, test_expression,
-		Monkey, Operand, Operation, Operator, TestExpression,
+		expression, gen, monkey, monkey_business, monkeys, operation, solve_part1, solve_part2,
+		starting_items, test_expression, trace, Arithmetic, Expression, Monkey, Operator, Residue,
+		TestExpression, WorryError,
 	};
 
 	const EXAMPLE: &str = r"Monkey 0:
@@ -235,6 +618,11 @@ Monkey 3:
 
 	#[test]
 	fn parsing_tests() {
+		let by_zero = r"  Test: divisible by 0
+    If true: throw to monkey 2
+    If false: throw to monkey 3";
+		assert!(test_expression(by_zero).is_err());
+
 		let monkey0 = r"  Test: divisible by 23
     If true: throw to monkey 2
     If false: throw to monkey 3";
@@ -301,35 +689,175 @@ Monkey 3:
 		assert_eq!(
 			Ok((
 				"",
-				Operation {
-					operator: Operator::Times,
-					operand: Operand::Value(19)
-				}
+				Expression::binary(Operator::Times, Expression::Old, Expression::Value(19))
 			)),
 			operation("  Operation: new = old * 19")
 		);
 		assert_eq!(
 			Ok((
 				"",
-				Operation {
-					operator: Operator::Plus,
-					operand: Operand::Value(6)
-				}
+				Expression::binary(Operator::Plus, Expression::Old, Expression::Value(6))
 			)),
 			operation("  Operation: new = old + 6")
 		);
 		assert_eq!(
 			Ok((
 				"",
-				Operation {
-					operator: Operator::Times,
-					operand: Operand::Old
-				}
+				Expression::binary(Operator::Times, Expression::Old, Expression::Old)
 			)),
 			operation("  Operation: new = old * old")
 		);
 	}
 
+	#[test]
+	fn parsing_expressions() {
+		let (tail, square_plus_3) = expression("old * old + 3").unwrap();
+		assert_eq!("", tail);
+		assert_eq!(
+			Expression::binary(
+				Operator::Plus,
+				Expression::binary(Operator::Times, Expression::Old, Expression::Old),
+				Expression::Value(3)
+			),
+			square_plus_3
+		);
+		assert_eq!(Some(103), square_plus_3.evaluate(10));
+
+		let (_, nested) = expression("( old - 2 )*(old+1) - 4").unwrap();
+		assert_eq!(Some(126), nested.evaluate(12));
+		assert_eq!(None, nested.evaluate_with(&Residue::new(12, 7)));
+		assert_eq!(None, nested.evaluate(1));
+
+		let (_, sum_of_products) = expression("(old + 2) * (old+1) + 4").unwrap();
+		assert_eq!(
+			Some(Residue::new(186, 7)),
+			sum_of_products.evaluate_with(&Residue::new(12 + 7 * 5, 7))
+		);
+
+		let (_, left_associative) = expression("old - 5 - 3").unwrap();
+		assert_eq!(Some(2), left_associative.evaluate(10));
+
+		assert!(expression("(old * 2").is_err());
+		assert!(expression("new + 1").is_err());
+	}
+
+	#[test]
+	fn congruence() {
+		assert!(expression("old * (old + 1) + 7")
+			.unwrap()
+			.1
+			.preserves_congruence());
+		assert!(!expression("old * (old - 1) + 7")
+			.unwrap()
+			.1
+			.preserves_congruence());
+		assert!(!expression("old * 2 / 3").unwrap().1.preserves_congruence());
+
+		// Reducing worry levels with a dividing operation would give 1599
+		let mut barrel = gen(EXAMPLE);
+		barrel[3].operation = expression("(old + 6) / 2").unwrap().1;
+		assert_eq!(
+			Err(WorryError::NotCongruent),
+			monkey_business(&barrel, 8, &Expression::Old, Arithmetic::Modular)
+		);
+		assert_eq!(
+			Ok(1600),
+			monkey_business(&barrel, 8, &Expression::Old, Arithmetic::Checked)
+		);
+
+		// Too many large tests to multiply together
+		let mut barrel = gen(EXAMPLE);
+		barrel[0].test.divisible_by = u64::MAX / 2;
+		barrel[1].test.divisible_by = 3;
+		assert_eq!(
+			Err(WorryError::ModulusOverflow),
+			monkey_business(&barrel, 8, &Expression::Old, Arithmetic::Modular)
+		);
+
+		// Subtracting is refused by modular arithmetic rather than wrapping around
+		let mut barrel = gen(EXAMPLE);
+		barrel[3].operation = expression("old - 3").unwrap().1;
+		assert_eq!(
+			Err(WorryError::NotCongruent),
+			monkey_business(&barrel, 8, &Expression::Old, Arithmetic::Modular)
+		);
+		let checked = monkey_business(&barrel, 8, &Expression::Old, Arithmetic::Checked);
+		assert!(checked.is_ok());
+		assert_eq!(
+			checked,
+			monkey_business(&barrel, 8, &Expression::Old, Arithmetic::Exact)
+		);
+	}
+
+	#[test]
+	fn worry_arithmetic() {
+		let barrel = gen(EXAMPLE);
+		let no_relief = Expression::Old;
+		for arithmetic in [Arithmetic::Modular, Arithmetic::Checked, Arithmetic::Exact] {
+			assert_eq!(
+				Ok(3600),
+				monkey_business(&barrel, 12, &no_relief, arithmetic)
+			);
+		}
+		assert_eq!(
+			Err(WorryError::Overflow {
+				round: 13,
+				monkey: 0
+			}),
+			monkey_business(&barrel, 20, &no_relief, Arithmetic::Checked)
+		);
+
+		let relief = expression("old / 3").unwrap().1;
+		assert_eq!(
+			Ok(10605),
+			monkey_business(&barrel, 20, &relief, Arithmetic::Exact)
+		);
+		assert_eq!(
+			Err(WorryError::NotCongruent),
+			monkey_business(&barrel, 20, &relief, Arithmetic::Modular)
+		);
+	}
+
+	#[test]
+	fn tracing() {
+		let barrel = gen(EXAMPLE);
+		let relief = expression("old / 3").unwrap().1;
+		let trace = trace(&barrel, 20, &relief, Arithmetic::Checked).unwrap();
+
+		assert_eq!(10, trace.items.len());
+		assert_eq!(20, trace.rounds.len());
+		assert_eq!(Some(&[2, 4, 3, 5][..]), trace.round(1));
+		assert_eq!(None, trace.round(0));
+		assert_eq!(vec![101, 95, 7, 105], trace.inspections());
+		assert_eq!(10605, trace.monkey_business());
+
+		// The first item goes 79 * 19 / 3 = 500 to monkey 3, then (500 + 3) / 3 = 167 to monkey 1
+		assert_eq!(
+			Some(&[0, 3, 1][..]),
+			trace.path(0).as_deref().map(|p| &p[..3])
+		);
+		assert_eq!(
+			vec![(0, 79), (1, 500), (1, 167)],
+			trace.items[0][..3]
+				.iter()
+				.map(|visit| (visit.round, u64::try_from(&visit.worry_level).unwrap()))
+				.collect::<Vec<_>>()
+		);
+		assert!(trace.visits_to(3).all(|(_, visit)| visit.monkey == 3));
+
+		let items_csv = trace.items_csv();
+		let mut lines = items_csv.lines();
+		assert_eq!(Some("item,step,round,monkey,worry_level"), lines.next());
+		assert_eq!(Some("0,0,0,0,79"), lines.next());
+		assert_eq!(Some("0,1,1,3,500"), lines.next());
+
+		let rounds_csv = trace.rounds_csv();
+		assert_eq!(
+			vec!["round,monkey0,monkey1,monkey2,monkey3", "1,2,4,3,5"],
+			rounds_csv.lines().take(2).collect::<Vec<_>>()
+		);
+	}
+
 	#[test]
 	fn test_monkey() {
 		let monkey0 = r"Monkey 0:
@@ -341,10 +869,7 @@ Monkey 3:
 		let expected = Monkey {
 			number: 0,
 			starting_items: vec![79, 98],
-			operation: Operation {
-				operator: Operator::Times,
-				operand: Operand::Value(19),
-			},
+			operation: Expression::binary(Operator::Times, Expression::Old, Expression::Value(19)),
 			test: TestExpression {
 				divisible_by: 23,
 				if_true: 2,
@@ -362,10 +887,7 @@ Monkey 3:
 		let expected = Monkey {
 			number: 3,
 			starting_items: vec![74],
-			operation: Operation {
-				operator: Operator::Plus,
-				operand: Operand::Value(3),
-			},
+			operation: Expression::binary(Operator::Plus, Expression::Old, Expression::Value(3)),
 			test: TestExpression {
 				divisible_by: 17,
 				if_true: 0,
@@ -385,11 +907,11 @@ Monkey 3:
 
 	#[test]
 	fn part1() {
-		assert_eq!(10605, solve_part1(&gen(EXAMPLE)));
+		assert_eq!(Ok(10605), solve_part1(&gen(EXAMPLE)));
 	}
 
 	#[test]
 	fn part2() {
-		assert_eq!(2713310158, solve_part2(&gen(EXAMPLE)));
+		assert_eq!(Ok(2713310158), solve_part2(&gen(EXAMPLE)));
 	}
 }
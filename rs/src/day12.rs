@@ -1,6 +1,7 @@
 use pathfinding::matrix::Matrix;
-use petgraph::algo::astar;
-use petgraph::prelude::DiGraphMap;
+use std::cmp::Ordering;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
 use std::fmt;
 use yaah::*;
 
@@ -26,60 +27,303 @@ fn gen(input: &'static str) -> Matrix<Elevation> {
 }
 
 #[aoc(day12, part1)]
-fn solve_part1(map: &Matrix<Elevation>) -> u32 {
-	hike(map, 'S', 'E')
+fn solve_part1(map: &Matrix<Elevation>) -> Option<u32> {
+	let hike = Hike::new('S', 'E');
+	#[cfg(feature = "debug")]
+	println!("{}", hike.route(map)?.render(map));
+	hike.cost(map)
 }
 
 #[aoc(day12, part2)]
-fn solve_part2(map: &Matrix<Elevation>) -> u32 {
-	hike(map, 'E', 'a')
+fn solve_part2(map: &Matrix<Elevation>) -> Option<u32> {
+	let hike = Hike::new('a', 'E');
+	#[cfg(feature = "debug")]
+	{
+		let route = hike.route(map)?;
+		println!("starting from {}\n{}", route.start(), route.render(map));
+	}
+	hike.cost(map)
 }
 
-fn hike(map: &Matrix<Elevation>, from: char, to: char) -> u32 {
-	let mut graph: DiGraphMap<Elevation, u32> = DiGraphMap::new();
+/// Which steps can be taken and what they cost
+pub trait ClimbRule {
+	/// Cost of stepping from `from` onto its neighbour `to`, `None` if it can't be done
+	fn cost(&self, from: &Elevation, to: &Elevation) -> Option<u32>;
 
-	let edges = map
-		.items()
-		.flat_map(|(_, &elevation)| {
-			successors(map, elevation)
-				.iter()
-				.map(|&other| (elevation, other, 1u32))
-				.collect::<Vec<(Elevation, Elevation, u32)>>()
-		})
-		.collect::<Vec<(Elevation, Elevation, u32)>>();
+	/// The cost of every step when they all cost the same, so a breadth first search will do
+	fn uniform_cost(&self) -> Option<u32> {
+		None
+	}
+}
 
-	for (left, right, weight) in edges {
-		match from {
-			'S' => graph.add_edge(left, right, weight),
-			_ => graph.add_edge(right, left, weight),
-		};
+/// To avoid needing to get out your climbing gear,
+/// the elevation of the destination square can be at most one higher than the elevation of your current square;
+/// that is, if your current elevation is m, you could step to elevation n, but not to elevation o.
+/// (This also means that the elevation of the destination square can be much lower than the elevation of your current square.)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Standard;
+
+impl ClimbRule for Standard {
+	fn cost(&self, from: &Elevation, to: &Elevation) -> Option<u32> {
+		(to.height_value() <= from.height_value() + 1).then_some(1)
 	}
 
-	let start = graph.nodes().find(|n| n.height == from).unwrap();
+	fn uniform_cost(&self) -> Option<u32> {
+		Some(1)
+	}
+}
 
-	let (steps, _) = astar(&graph, start, |e| e.height == to, |_| 1, |_| 0).unwrap();
+/// Limits on how far a step can climb or drop, each unit of height changed adding to its cost
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Weighted {
+	pub max_ascent: u32,
+	pub max_descent: u32,
+	pub step: u32,
+	pub per_ascent: u32,
+	pub per_descent: u32,
+}
 
-	steps as u32
+/// The same steps as [`Standard`]
+impl Default for Weighted {
+	fn default() -> Self {
+		Weighted {
+			max_ascent: 1,
+			max_descent: u32::MAX,
+			step: 1,
+			per_ascent: 0,
+			per_descent: 0,
+		}
+	}
 }
 
-fn successors(map: &Matrix<Elevation>, elevation: Elevation) -> Vec<Elevation> {
-	let horses = map
-		.neighbours((elevation.row, elevation.column), false)
-		.map(|rc| *map.get(rc).unwrap())
-		.filter(|neighbor| is_passable(&elevation, neighbor))
-		.collect::<Vec<Elevation>>();
-	match horses.iter().find(|&e| e.height == 'E') {
-		Some(&end) => vec![end],
-		None => horses,
+impl ClimbRule for Weighted {
+	fn cost(&self, from: &Elevation, to: &Elevation) -> Option<u32> {
+		let (from, to) = (from.height_value(), to.height_value());
+		match to.cmp(&from) {
+			Ordering::Greater if to - from <= self.max_ascent => (to - from)
+				.checked_mul(self.per_ascent)?
+				.checked_add(self.step),
+			Ordering::Less if from - to <= self.max_descent => (from - to)
+				.checked_mul(self.per_descent)?
+				.checked_add(self.step),
+			Ordering::Equal => Some(self.step),
+			_ => None,
+		}
+	}
+
+	fn uniform_cost(&self) -> Option<u32> {
+		(self.per_ascent == 0 && self.per_descent == 0).then_some(self.step)
 	}
 }
 
-/// To avoid needing to get out your climbing gear,
-/// the elevation of the destination square can be at most one higher than the elevation of your current square;
-/// that is, if your current elevation is m, you could step to elevation n, but not to elevation o.
-/// (This also means that the elevation of the destination square can be much lower than the elevation of your current square.)
-fn is_passable(e1: &Elevation, e2: &Elevation) -> bool {
-	e2.height_value() <= e1.height_value() + 1
+/// Cheapest way from any `start` cell to a `goal` cell under a [`ClimbRule`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Hike<R = Standard> {
+	pub start: char,
+	pub goal: char,
+	pub rule: R,
+}
+
+impl Hike {
+	pub fn new(start: char, goal: char) -> Self {
+		Hike {
+			start,
+			goal,
+			rule: Standard,
+		}
+	}
+}
+
+impl<R: ClimbRule> Hike<R> {
+	pub fn with_rule<S: ClimbRule>(self, rule: S) -> Hike<S> {
+		Hike {
+			start: self.start,
+			goal: self.goal,
+			rule,
+		}
+	}
+
+	pub fn field(&self, map: &Matrix<Elevation>) -> DistanceField {
+		DistanceField::new(map, &self.rule, |e| e.height == self.goal)
+	}
+
+	/// Route from the cheapest start cell
+	pub fn route(&self, map: &Matrix<Elevation>) -> Option<Route> {
+		let field = self.field(map);
+		let start = field.nearest(map, |e| e.height == self.start)?;
+		field.route(map, &start)
+	}
+
+	pub fn cost(&self, map: &Matrix<Elevation>) -> Option<u32> {
+		let field = self.field(map);
+		field.distance(&field.nearest(map, |e| e.height == self.start)?)
+	}
+}
+
+/// Cost from every cell to the nearest target, found by searching backwards from the targets
+#[derive(Debug, Clone)]
+pub struct DistanceField {
+	distances: Matrix<Option<u32>>,
+	/// The neighbour one step closer to a target
+	next: Matrix<Option<(usize, usize)>>,
+}
+
+impl DistanceField {
+	pub fn to_end(map: &Matrix<Elevation>) -> Self {
+		Self::new(map, &Standard, |e| e.height == 'E')
+	}
+
+	/// Breadth first when every step costs the same, otherwise Dijkstra
+	pub fn new(
+		map: &Matrix<Elevation>,
+		rule: &impl ClimbRule,
+		is_target: impl Fn(&Elevation) -> bool,
+	) -> Self {
+		let targets: Vec<(usize, usize)> = map
+			.items()
+			.filter(|(_, elevation)| is_target(elevation))
+			.map(|(position, _)| position)
+			.collect();
+		match rule.uniform_cost() {
+			Some(step) => Self::breadth_first(map, rule, step, targets),
+			None => Self::dijkstra(map, rule, targets),
+		}
+	}
+
+	fn breadth_first(
+		map: &Matrix<Elevation>,
+		rule: &impl ClimbRule,
+		step: u32,
+		targets: Vec<(usize, usize)>,
+	) -> Self {
+		let mut distances: Matrix<Option<u32>> = Matrix::new(map.rows, map.columns, None);
+		let mut next = Matrix::new(map.rows, map.columns, None);
+		targets
+			.iter()
+			.for_each(|position| distances[*position] = Some(0));
+		let mut queue = VecDeque::from(targets);
+
+		while let Some(position) = queue.pop_front() {
+			let Some(distance) = distances[position].unwrap().checked_add(step) else {
+				continue;
+			};
+			for neighbour in map.neighbours(position, false) {
+				// Searching backwards, so the step is from the neighbour
+				if distances[neighbour].is_none()
+					&& rule.cost(&map[neighbour], &map[position]).is_some()
+				{
+					distances[neighbour] = Some(distance);
+					next[neighbour] = Some(position);
+					queue.push_back(neighbour);
+				}
+			}
+		}
+		DistanceField { distances, next }
+	}
+
+	fn dijkstra(
+		map: &Matrix<Elevation>,
+		rule: &impl ClimbRule,
+		targets: Vec<(usize, usize)>,
+	) -> Self {
+		let mut distances = Matrix::new(map.rows, map.columns, None);
+		let mut next = Matrix::new(map.rows, map.columns, None);
+		let mut queue: BinaryHeap<Reverse<(u32, (usize, usize))>> = BinaryHeap::new();
+		for position in targets {
+			distances[position] = Some(0);
+			queue.push(Reverse((0, position)));
+		}
+
+		while let Some(Reverse((distance, position))) = queue.pop() {
+			if distances[position].is_some_and(|best| distance > best) {
+				continue;
+			}
+			for neighbour in map.neighbours(position, false) {
+				// Searching backwards, so the step is from the neighbour
+				let Some(cost) = rule.cost(&map[neighbour], &map[position]) else {
+					continue;
+				};
+				// Too costly to count is as good as unreachable
+				let Some(candidate) = distance.checked_add(cost) else {
+					continue;
+				};
+				if distances[neighbour].is_none_or(|best| candidate < best) {
+					distances[neighbour] = Some(candidate);
+					next[neighbour] = Some(position);
+					queue.push(Reverse((candidate, neighbour)));
+				}
+			}
+		}
+		DistanceField { distances, next }
+	}
+
+	/// Cost to the nearest target, `None` if none can be reached
+	pub fn distance(&self, from: &Elevation) -> Option<u32> {
+		self.distances[(from.row, from.column)]
+	}
+
+	/// The cell matching `is_start` closest to a target
+	pub fn nearest(
+		&self,
+		map: &Matrix<Elevation>,
+		is_start: impl Fn(&Elevation) -> bool,
+	) -> Option<Elevation> {
+		map.values()
+			.filter(|elevation| is_start(elevation))
+			.filter_map(|elevation| Some((self.distance(elevation)?, *elevation)))
+			.min_by_key(|(distance, _)| *distance)
+			.map(|(_, elevation)| elevation)
+	}
+
+	pub fn route(&self, map: &Matrix<Elevation>, from: &Elevation) -> Option<Route> {
+		self.distance(from)?;
+		let mut route = vec![*from];
+		let mut position = (from.row, from.column);
+		while let Some(next) = self.next[position] {
+			route.push(map[next]);
+			position = next;
+		}
+		Some(Route(route))
+	}
+}
+
+/// Elevations visited in order, including both ends
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Route(pub Vec<Elevation>);
+
+impl Route {
+	pub fn steps(&self) -> usize {
+		self.0.len().saturating_sub(1)
+	}
+
+	pub fn start(&self) -> Elevation {
+		self.0[0]
+	}
+
+	pub fn end(&self) -> Elevation {
+		*self.0.last().unwrap()
+	}
+
+	/// Draws the route over the map like the puzzle, each cell pointing to the next with the goal marked as on the map
+	pub fn render(&self, map: &Matrix<Elevation>) -> String {
+		let mut cells = vec![vec!['.'; map.columns]; map.rows];
+		for (here, next) in self.0.iter().zip(self.0.iter().skip(1)) {
+			cells[here.row][here.column] =
+				match (next.row.cmp(&here.row), next.column.cmp(&here.column)) {
+					(Ordering::Greater, _) => 'v',
+					(Ordering::Less, _) => '^',
+					(_, Ordering::Greater) => '>',
+					_ => '<',
+				};
+		}
+		let end = self.end();
+		cells[end.row][end.column] = end.height;
+		cells
+			.iter()
+			.map(|row| row.iter().collect::<String>())
+			.collect::<Vec<String>>()
+			.join("\n")
+	}
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Ord, PartialOrd)]
@@ -96,7 +340,20 @@ impl fmt::Display for Elevation {
 }
 
 impl Elevation {
-	fn height_value(&self) -> u32 {
+	pub fn row(&self) -> usize {
+		self.row
+	}
+
+	pub fn column(&self) -> usize {
+		self.column
+	}
+
+	pub fn height(&self) -> char {
+		self.height
+	}
+
+	/// Height as a number, `S` being as low as `a` and `E` as high as `z`
+	pub fn height_value(&self) -> u32 {
 		match self.height {
 			'E' => 'z' as u32,
 			'S' => 'a' as u32,
@@ -107,7 +364,7 @@ impl Elevation {
 
 #[cfg(test)]
 mod test {
-	use crate::day12::{gen, solve_part1, solve_part2};
+	use crate::day12::{gen, solve_part1, solve_part2, ClimbRule, DistanceField, Hike, Weighted};
 
 	const EXAMPLE: &str = r"Sabqponm
 abcryxxl
@@ -123,11 +380,130 @@ abdefghi";
 
 	#[test]
 	fn part1() {
-		assert_eq!(31, solve_part1(&gen(EXAMPLE)));
+		assert_eq!(Some(31), solve_part1(&gen(EXAMPLE)));
 	}
 
 	#[test]
 	fn part2() {
-		assert_eq!(29, solve_part2(&gen(EXAMPLE)));
+		assert_eq!(Some(29), solve_part2(&gen(EXAMPLE)));
+	}
+
+	#[test]
+	fn route() {
+		let map = gen(EXAMPLE);
+		let route = Hike::new('S', 'E').route(&map).unwrap();
+		assert_eq!(31, route.steps());
+		assert_eq!(
+			('S', 0, 0),
+			(
+				route.start().height(),
+				route.start().row(),
+				route.start().column()
+			)
+		);
+		assert_eq!(
+			('E', 2, 5),
+			(
+				route.end().height(),
+				route.end().row(),
+				route.end().column()
+			)
+		);
+
+		// Every step is to a neighbour
+		assert!(route.0.windows(2).all(|pair| {
+			pair[0].row().abs_diff(pair[1].row()) + pair[0].column().abs_diff(pair[1].column()) == 1
+		}));
+
+		// An equally short route to the one drawn in the puzzle
+		let expected = r">>vv<<<<
+..vvv<<^
+..vv>E^^
+..v>>>^^
+..>>>>>^";
+		assert_eq!(expected, route.render(&map));
+	}
+
+	#[test]
+	fn scenic_route() {
+		let map = gen(EXAMPLE);
+		let route = Hike::new('a', 'E').route(&map).unwrap();
+		assert_eq!(29, route.steps());
+		assert_eq!('a', route.start().height());
+		assert_eq!('E', route.end().height());
+		assert_eq!((4, 0), (route.start().row(), route.start().column()));
+	}
+
+	#[test]
+	fn distance_field() {
+		let map = gen(EXAMPLE);
+		let field = DistanceField::to_end(&map);
+		assert_eq!(Some(0), field.distance(&map[(2, 5)]));
+		assert_eq!(Some(31), field.distance(&map[(0, 0)]));
+		assert_eq!(Some(29), field.distance(&map[(4, 0)]));
+		assert!(map
+			.values()
+			.all(|e| field.route(&map, e).map(|route| route.steps() as u32) == field.distance(e)));
+
+		// A wall of `z` can be walked down but not climbed
+		let walled = gen("Sbz\nabz\nzzE");
+		let field = DistanceField::to_end(&walled);
+		assert_eq!(None, field.distance(&walled[(0, 0)]));
+		assert_eq!(Some(1), field.distance(&walled[(1, 2)]));
+		assert_eq!(None, Hike::new('S', 'E').route(&walled));
+
+		let field = DistanceField::new(&map, &Weighted::default(), |e| {
+			e.height() == 'a' || e.height() == 'c'
+		});
+		assert_eq!(Some(0), field.distance(&map[(1, 0)]));
+		assert_eq!(Some(1), field.distance(&map[(0, 0)]));
+	}
+
+	#[test]
+	fn climbing_rules() {
+		// Along the top is shorter, along the bottom climbs less and never drops
+		let map = gen("Sbcbcd\nabbccd");
+		let hike = Hike::new('S', 'd');
+		assert_eq!(Some(5), hike.cost(&map));
+		assert_eq!(Some(5), hike.with_rule(Weighted::default()).cost(&map));
+		assert_eq!(Some(1), Weighted::default().uniform_cost());
+
+		// Every step costing the same is still searched breadth first
+		let double = hike.with_rule(Weighted {
+			step: 2,
+			..Weighted::default()
+		});
+		assert_eq!(Some(10), double.cost(&map));
+
+		let steep = hike.with_rule(Weighted {
+			per_ascent: 10,
+			..Weighted::default()
+		});
+		assert_eq!(None, steep.rule.uniform_cost());
+		assert_eq!(Some(36), steep.cost(&map));
+		assert_eq!(">v....\n.>>>>d", steep.route(&map).unwrap().render(&map));
+
+		let no_descent = hike.with_rule(Weighted {
+			max_descent: 0,
+			..Weighted::default()
+		});
+		assert_eq!(Some(6), no_descent.cost(&map));
+
+		let too_steep = hike.with_rule(Weighted {
+			max_ascent: 0,
+			..Weighted::default()
+		});
+		assert_eq!(None, too_steep.cost(&map));
+
+		let overflowing = hike.with_rule(Weighted {
+			per_ascent: u32::MAX,
+			..Weighted::default()
+		});
+		assert_eq!(None, overflowing.cost(&map));
+		let costly = hike.with_rule(Weighted {
+			step: u32::MAX / 4,
+			..Weighted::default()
+		});
+		assert_eq!(None, costly.cost(&map));
 	}
 }
@@ -1,5 +1,10 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
 use yaah::*;
 
+/// Elves carry their badges in groups of this many rucksacks
+pub const GROUP_SIZE: usize = 3;
+
 #[aoc_generator(day3, part1)]
 fn gen(input: &'static str) -> Vec<Rucksack> {
 	input.lines().map(Rucksack::new).collect()
@@ -23,12 +28,29 @@ fn gen_two(input: &'static str) -> Vec<String> {
 }
 
 #[aoc(day3, part2)]
-fn solve_part2(lines: &[String]) -> i32 {
+fn solve_part2(lines: &[String]) -> Result<i32, BadgeError> {
+	badge_priorities(lines, GROUP_SIZE)
+}
+
+/// Sums the priorities of the badge shared by each group of `group_size` rucksacks
+pub fn badge_priorities(lines: &[String], group_size: usize) -> Result<i32, BadgeError> {
+	if group_size == 0 {
+		return Err(BadgeError::InvalidGroupSize);
+	}
 	lines
-		.chunks(3)
-		.map(|c| c.to_vec())
-		.map(common_item)
-		.map(item_type_value)
+		.chunks(group_size)
+		.enumerate()
+		.map(|(group, rucksacks)| {
+			if rucksacks.len() == group_size {
+				common_item(rucksacks).map_err(|e| e.in_group(group))
+			} else {
+				Err(BadgeError::IncompleteGroup {
+					group,
+					size: rucksacks.len(),
+				})
+			}
+		})
+		.map(|badge| badge.map(item_type_value))
 		.sum()
 }
 
@@ -50,6 +72,127 @@ impl Rucksack {
 	pub fn shared_item(&self) -> char {
 		shared_item(&self.compartment1, &self.compartment2)
 	}
+
+	/// Every item type found in both compartments
+	pub fn shared_items(&self) -> ItemSet {
+		ItemSet::from(self.compartment1.as_str()) & ItemSet::from(self.compartment2.as_str())
+	}
+
+	/// Every item type found in either compartment
+	pub fn items(&self) -> ItemSet {
+		ItemSet::from(self.compartment1.as_str()) | ItemSet::from(self.compartment2.as_str())
+	}
+}
+
+/// Set of item types stored as a 52-bit mask, bit `n` being the item with priority `n + 1`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ItemSet(u64);
+
+impl ItemSet {
+	const MASK: u64 = (1 << 52) - 1;
+
+	pub const fn empty() -> Self {
+		ItemSet(0)
+	}
+
+	pub const fn all() -> Self {
+		ItemSet(Self::MASK)
+	}
+
+	/// Adds the item type, ignoring anything that isn't a-z or A-Z
+	pub fn insert(&mut self, item: char) {
+		if let Some(bit) = item_bit(item) {
+			self.0 |= bit;
+		}
+	}
+
+	pub fn contains(&self, item: char) -> bool {
+		item_bit(item).is_some_and(|bit| self.0 & bit != 0)
+	}
+
+	pub fn len(&self) -> usize {
+		self.0.count_ones() as usize
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0 == 0
+	}
+
+	pub fn intersection(self, other: ItemSet) -> ItemSet {
+		ItemSet(self.0 & other.0)
+	}
+
+	pub fn union(self, other: ItemSet) -> ItemSet {
+		ItemSet(self.0 | other.0)
+	}
+
+	/// Item types present in every one of the sets
+	pub fn intersection_of<I: IntoIterator<Item = ItemSet>>(sets: I) -> ItemSet {
+		sets.into_iter().fold(ItemSet::all(), ItemSet::intersection)
+	}
+
+	/// Item types present in any of the sets
+	pub fn union_of<I: IntoIterator<Item = ItemSet>>(sets: I) -> ItemSet {
+		sets.into_iter().fold(ItemSet::empty(), ItemSet::union)
+	}
+
+	/// The item type, if the set holds exactly one
+	pub fn single(&self) -> Option<char> {
+		match self.len() {
+			1 => self.items().next(),
+			_ => None,
+		}
+	}
+
+	/// Item types in priority order
+	pub fn items(&self) -> impl Iterator<Item = char> + '_ {
+		('a'..='z')
+			.chain('A'..='Z')
+			.filter(|item| self.contains(*item))
+	}
+}
+
+impl fmt::Display for ItemSet {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.items().try_for_each(|item| write!(f, "{item}"))
+	}
+}
+
+impl Serialize for ItemSet {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+impl From<&str> for ItemSet {
+	fn from(s: &str) -> Self {
+		let mut set = ItemSet::empty();
+		s.chars().for_each(|item| set.insert(item));
+		set
+	}
+}
+
+impl std::ops::BitAnd for ItemSet {
+	type Output = ItemSet;
+
+	fn bitand(self, rhs: Self) -> Self::Output {
+		self.intersection(rhs)
+	}
+}
+
+impl std::ops::BitOr for ItemSet {
+	type Output = ItemSet;
+
+	fn bitor(self, rhs: Self) -> Self::Output {
+		self.union(rhs)
+	}
+}
+
+fn item_bit(item: char) -> Option<u64> {
+	match item_type_value(item) {
+		0 => None,
+		priority => Some(1 << (priority - 1)),
+	}
 }
 
 pub fn item_type_value(c: char) -> i32 {
@@ -60,25 +203,136 @@ pub fn item_type_value(c: char) -> i32 {
 	}
 }
 
-pub fn common_item(group: Vec<String>) -> char {
-	let (s1, s2, s3) = if let [s1, s2, s3] = group.as_slice() {
-		(s1, s2, s3)
-	} else {
-		panic!()
-	};
-	s1.chars()
-		.find(|c| s2.contains(*c) && s3.contains(*c))
-		.unwrap()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BadgeError {
+	InvalidGroupSize,
+	IncompleteGroup { group: usize, size: usize },
+	NoCommonItem { group: usize },
+	MultipleCommonItems { group: usize, items: String },
+}
+
+impl BadgeError {
+	fn in_group(self, group: usize) -> Self {
+		match self {
+			BadgeError::NoCommonItem { .. } => BadgeError::NoCommonItem { group },
+			BadgeError::MultipleCommonItems { items, .. } => {
+				BadgeError::MultipleCommonItems { group, items }
+			}
+			e => e,
+		}
+	}
+}
+
+impl fmt::Display for BadgeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			BadgeError::InvalidGroupSize => write!(f, "group size must be at least 1"),
+			BadgeError::IncompleteGroup { group, size } => {
+				write!(f, "group {group} only has {size} rucksacks")
+			}
+			BadgeError::NoCommonItem { group } => {
+				write!(f, "group {group} has no item in common")
+			}
+			BadgeError::MultipleCommonItems { group, items } => {
+				write!(f, "group {group} has several items in common: {items}")
+			}
+		}
+	}
+}
+
+/// Finds the badge: the single item type carried by every rucksack in the group
+pub fn common_item<S: AsRef<str>>(group: &[S]) -> Result<char, BadgeError> {
+	let common = ItemSet::intersection_of(group.iter().map(|s| ItemSet::from(s.as_ref())));
+	match common.len() {
+		0 => Err(BadgeError::NoCommonItem { group: 0 }),
+		1 => Ok(common.single().unwrap()),
+		_ => Err(BadgeError::MultipleCommonItems {
+			group: 0,
+			items: common.items().collect(),
+		}),
+	}
 }
 
 pub fn shared_item(s1: &str, s2: &str) -> char {
-	s1.chars().find(|c| s2.contains(*c)).unwrap()
+	(ItemSet::from(s1) & ItemSet::from(s2))
+		.items()
+		.next()
+		.unwrap()
+}
+
+/// Inventory audit over every rucksack and badge group
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+	pub rucksacks: Vec<RucksackAudit>,
+	pub groups: Vec<GroupAudit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RucksackAudit {
+	pub index: usize,
+	pub len: usize,
+	/// Every item found in both compartments, not just the first
+	pub shared_items: ItemSet,
+	/// Odd number of items, so the middle item could belong to either compartment
+	pub ambiguous_split: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupAudit {
+	pub group: usize,
+	pub rucksacks: Vec<usize>,
+	/// Items carried by every rucksack in the group
+	pub candidate_badges: ItemSet,
+}
+
+impl AuditReport {
+	/// Groups of `group_size` rucksacks, which has to be at least one
+	pub fn new(rucksacks: &[Rucksack], group_size: usize) -> Result<Self, BadgeError> {
+		if group_size == 0 {
+			return Err(BadgeError::InvalidGroupSize);
+		}
+		let audits = rucksacks
+			.iter()
+			.enumerate()
+			.map(|(index, rucksack)| {
+				let len = rucksack.compartment1.len() + rucksack.compartment2.len();
+				RucksackAudit {
+					index,
+					len,
+					shared_items: rucksack.shared_items(),
+					ambiguous_split: len % 2 == 1,
+				}
+			})
+			.collect();
+		let groups = rucksacks
+			.chunks(group_size)
+			.enumerate()
+			.map(|(group, sacks)| GroupAudit {
+				group,
+				rucksacks: (group * group_size..group * group_size + sacks.len()).collect(),
+				candidate_badges: ItemSet::intersection_of(sacks.iter().map(Rucksack::items)),
+			})
+			.collect();
+		Ok(AuditReport {
+			rucksacks: audits,
+			groups,
+		})
+	}
+
+	pub fn ambiguous_splits(&self) -> impl Iterator<Item = &RucksackAudit> {
+		self.rucksacks.iter().filter(|r| r.ambiguous_split)
+	}
+
+	pub fn to_json(&self) -> serde_json::Result<String> {
+		serde_json::to_string_pretty(self)
+	}
 }
 
 #[cfg(test)]
 mod test {
 	use crate::day3::{
-		common_item, gen, gen_two, item_type_value, shared_item, solve_part1, solve_part2, Rucksack,
+		badge_priorities, common_item, gen, gen_two, item_type_value, shared_item, solve_part1,
+		solve_part2, AuditReport, BadgeError, ItemSet, Rucksack, GROUP_SIZE,
 	};
 	use std::iter::zip;
 
@@ -186,12 +440,93 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
 	fn part2_examples() {
 		let lines = gen_two(EXAMPLE_2);
 
-		let group1 = lines[..3].to_vec();
-		assert_eq!('r', common_item(group1));
+		let group1 = &lines[..3];
+		assert_eq!(Ok('r'), common_item(group1));
+
+		let group2 = &lines[3..];
+		assert_eq!(Ok('Z'), common_item(group2));
+
+		assert_eq!(Ok(70), solve_part2(&lines))
+	}
+
+	#[test]
+	fn item_set() {
+		let set = ItemSet::from("vJrwpWtwJgWr");
+		assert_eq!(8, set.len());
+		assert!(set.contains('J'));
+		assert!(!set.contains('j'));
+
+		let shared = set & ItemSet::from("hcsFMMfFFhFp");
+		assert_eq!(Some('p'), shared.single());
+
+		let union = ItemSet::union_of([ItemSet::from("ab"), ItemSet::from("bC")]);
+		assert_eq!("abC", union.items().collect::<String>());
+		assert!(ItemSet::intersection_of([ItemSet::from("ab"), ItemSet::from("C")]).is_empty());
+	}
+
+	#[test]
+	fn badge_group_sizes() {
+		let lines = gen_two(EXAMPLE_2);
+
+		assert_eq!(Ok(18), badge_priorities(&lines[..3], 3));
+		assert_eq!(Ok('r'), common_item(&["abr", "rcd", "err", "rrf"]));
+		assert_eq!(
+			Err(BadgeError::MultipleCommonItems {
+				group: 0,
+				items: "frsFM".to_string()
+			}),
+			common_item(&lines[..2])
+		);
+		assert_eq!(
+			Err(BadgeError::NoCommonItem { group: 1 }),
+			badge_priorities(&["a", "a", "b", "c"].map(String::from), 2)
+		);
+		assert_eq!(
+			Err(BadgeError::IncompleteGroup { group: 1, size: 2 }),
+			badge_priorities(&lines[..5], 3)
+		);
+		assert_eq!(
+			Err(BadgeError::InvalidGroupSize),
+			badge_priorities(&lines, 0)
+		);
+	}
+
+	#[test]
+	fn audit_report() {
+		let mut rucksacks = gen(EXAMPLE);
+		rucksacks.push(Rucksack::new("abAcBab"));
+		let report = AuditReport::new(&rucksacks, GROUP_SIZE).unwrap();
+
+		let shared = report
+			.rucksacks
+			.iter()
+			.map(|r| r.shared_items.to_string())
+			.collect::<Vec<_>>();
+		assert_eq!(vec!["p", "L", "P", "v", "t", "s", "ab"], shared);
+
+		let ambiguous = report
+			.ambiguous_splits()
+			.map(|r| r.index)
+			.collect::<Vec<_>>();
+		assert_eq!(vec![6], ambiguous);
+
+		assert_eq!(3, report.groups.len());
+		assert_eq!("r", report.groups[0].candidate_badges.to_string());
+		assert_eq!("Z", report.groups[1].candidate_badges.to_string());
+		assert_eq!(vec![6], report.groups[2].rucksacks);
+		assert_eq!("abcAB", report.groups[2].candidate_badges.to_string());
 
-		let group2 = lines[3..].to_vec();
-		assert_eq!('Z', common_item(group2));
+		let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+		assert_eq!("L", json["rucksacks"][1]["shared_items"]);
+		assert_eq!(true, json["rucksacks"][6]["ambiguous_split"]);
+		assert_eq!("Z", json["groups"][1]["candidate_badges"]);
 
-		assert_eq!(70, solve_part2(&lines))
+		assert!(matches!(
+			AuditReport::new(&rucksacks, 0),
+			Err(BadgeError::InvalidGroupSize)
+		));
+		let singles = AuditReport::new(&rucksacks, 1).unwrap();
+		assert_eq!(7, singles.groups.len());
+		assert_eq!(vec![4], singles.groups[4].rucksacks);
 	}
 }
@@ -1,36 +1,91 @@
+use nom::bytes::complete::tag;
+use nom::character::complete::u32 as nom_u32;
+use nom::combinator::all_consuming;
+use nom::sequence::separated_pair;
+use nom::IResult;
+use nom::Parser;
+use std::fmt;
 use std::ops::RangeInclusive;
 use yaah::*;
 
 #[aoc_generator(day4)]
-fn gen(input: &'static str) -> Vec<String> {
-	input.lines().map(|s| s.to_string()).collect()
+fn gen(input: &'static str) -> Result<Vec<AssignmentPair>, AssignmentError> {
+	input
+		.lines()
+		.enumerate()
+		.map(|(i, line)| AssignmentPair::from_line(i + 1, line))
+		.collect()
 }
 
 #[aoc(day4, part1)]
-fn solve_part1(stringy_pairs: &[String]) -> usize {
-	stringy_pairs
-		.iter()
-		.map(|s| assignment_pair(s.as_str()))
-		.filter(|(l, r)| fully_contains(l.clone(), r.clone()))
-		.count()
+fn solve_part1(pairs: &[AssignmentPair]) -> usize {
+	pairs.iter().filter(|pair| pair.fully_contains()).count()
 }
 
 #[aoc(day4, part2)]
-fn solve_part2(stringy_pairs: &[String]) -> usize {
-	stringy_pairs
-		.iter()
-		.map(|s| assignment_pair(s.as_str()))
-		.filter(|(l, r)| overlap_at_all(l.clone(), r.clone()))
-		.count()
+fn solve_part2(pairs: &[AssignmentPair]) -> usize {
+	pairs.iter().filter(|pair| pair.overlaps()).count()
 }
 
-fn assignment_pair(s: &str) -> (RangeInclusive<u32>, RangeInclusive<u32>) {
-	let pair: Vec<RangeInclusive<u32>> = s
-		.split(',')
-		.map(|s| s.to_string())
-		.map(range_from_string)
-		.collect();
-	(pair[0].clone(), pair[1].clone())
+/// Section assignments for a pair of elves, each range validated to run low to high
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssignmentPair(pub RangeInclusive<u32>, pub RangeInclusive<u32>);
+
+impl AssignmentPair {
+	pub fn from_line(line: usize, s: &str) -> Result<Self, AssignmentError> {
+		let (_, (left, right)) =
+			all_consuming(assignment_pair)(s).map_err(|_| AssignmentError::Malformed {
+				line,
+				text: s.to_string(),
+			})?;
+		for range in [&left, &right] {
+			if range.start() > range.end() {
+				return Err(AssignmentError::Reversed {
+					line,
+					start: *range.start(),
+					end: *range.end(),
+				});
+			}
+		}
+		Ok(AssignmentPair(left, right))
+	}
+
+	pub fn fully_contains(&self) -> bool {
+		fully_contains(self.0.clone(), self.1.clone())
+	}
+
+	pub fn overlaps(&self) -> bool {
+		overlap_at_all(self.0.clone(), self.1.clone())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssignmentError {
+	Malformed { line: usize, text: String },
+	Reversed { line: usize, start: u32, end: u32 },
+}
+
+impl fmt::Display for AssignmentError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			AssignmentError::Malformed { line, text } => {
+				write!(f, "line {line}: expected `a-b,c-d` but found {text:?}")
+			}
+			AssignmentError::Reversed { line, start, end } => {
+				write!(f, "line {line}: section range {start}-{end} is reversed")
+			}
+		}
+	}
+}
+
+fn assignment_pair(input: &str) -> IResult<&str, (RangeInclusive<u32>, RangeInclusive<u32>)> {
+	separated_pair(section_range, tag(","), section_range)(input)
+}
+
+fn section_range(input: &str) -> IResult<&str, RangeInclusive<u32>> {
+	separated_pair(nom_u32, tag("-"), nom_u32)
+		.map(|(start, end)| start..=end)
+		.parse(input)
 }
 
 pub fn fully_contains(r1: RangeInclusive<u32>, r2: RangeInclusive<u32>) -> bool {
@@ -45,14 +100,118 @@ pub fn overlap_at_all(r1: RangeInclusive<u32>, r2: RangeInclusive<u32>) -> bool
 		|| r2.contains(r1.end())
 }
 
-fn range_from_string(s: String) -> RangeInclusive<u32> {
-	let r: Vec<u32> = s.split('-').filter_map(|s| s.parse::<u32>().ok()).collect();
-	RangeInclusive::new(r[0], r[1])
+/// Every assignment in the list, two elves per line
+pub fn assignments(pairs: &[AssignmentPair]) -> Vec<RangeInclusive<u32>> {
+	pairs
+		.iter()
+		.flat_map(|AssignmentPair(l, r)| [l.clone(), r.clone()])
+		.collect()
+}
+
+/// How many elves cover each section, across all assignments at once
+#[derive(Debug)]
+pub struct Coverage {
+	assignments: Vec<RangeInclusive<u32>>,
+	first: u32,
+	depths: Vec<usize>,
+}
+
+impl Coverage {
+	pub fn new(assignments: &[RangeInclusive<u32>]) -> Self {
+		let assignments = assignments.to_vec();
+		let sections = assignments.iter().filter(|r| !r.is_empty());
+		let first = sections.clone().map(|r| *r.start()).min().unwrap_or(0);
+		let len = sections
+			.clone()
+			.map(|r| (r.end() - first) as usize + 1)
+			.max()
+			.unwrap_or(0);
+
+		let mut deltas = vec![0isize; len + 1];
+		for range in sections {
+			deltas[(range.start() - first) as usize] += 1;
+			deltas[(range.end() - first) as usize + 1] -= 1;
+		}
+		let mut depth = 0isize;
+		let mut depths: Vec<usize> = deltas
+			.iter()
+			.map(|delta| {
+				depth += delta;
+				depth as usize
+			})
+			.collect();
+		depths.pop();
+
+		Coverage {
+			assignments,
+			first,
+			depths,
+		}
+	}
+
+	/// Number of elves assigned to the section
+	pub fn depth(&self, section: u32) -> usize {
+		section
+			.checked_sub(self.first)
+			.and_then(|i| self.depths.get(i as usize))
+			.copied()
+			.unwrap_or(0)
+	}
+
+	/// Every section between the lowest and highest assigned, with its depth
+	pub fn sections(&self) -> impl Iterator<Item = (u32, usize)> + '_ {
+		(self.first..).zip(self.depths.iter().copied())
+	}
+
+	pub fn max_depth(&self) -> usize {
+		self.depths.iter().copied().max().unwrap_or(0)
+	}
+
+	/// Runs of sections nobody is assigned to, between the lowest and highest assigned
+	pub fn gaps(&self) -> Vec<RangeInclusive<u32>> {
+		self.sections()
+			.filter(|(_, depth)| *depth == 0)
+			.map(|(section, _)| section)
+			.fold(vec![], |mut gaps: Vec<RangeInclusive<u32>>, section| {
+				match gaps.last_mut() {
+					Some(gap) if gap.end() + 1 == section => *gap = *gap.start()..=section,
+					_ => gaps.push(section..=section),
+				}
+				gaps
+			})
+	}
+
+	/// Elves whose sections are all covered by at least one other elf
+	pub fn redundant_elves(&self) -> Vec<usize> {
+		(0..self.assignments.len())
+			.filter(|elf| self.assignments[*elf].clone().all(|s| self.depth(s) > 1))
+			.collect()
+	}
+
+	/// A group of elves that can all be removed together without uncovering any section
+	pub fn redundant_group(&self) -> Vec<usize> {
+		let mut depths = self.depths.clone();
+		let index = |section: u32| (section - self.first) as usize;
+		self.redundant_elves()
+			.into_iter()
+			.filter(|elf| {
+				let range = self.assignments[*elf].clone();
+				let removable = range.clone().all(|s| depths[index(s)] > 1);
+				if removable {
+					range.for_each(|s| depths[index(s)] -= 1);
+				}
+				removable
+			})
+			.collect()
+	}
 }
 
 #[cfg(test)]
 mod test {
-	use crate::day4::{fully_contains, gen, range_from_string, solve_part1, solve_part2};
+	use crate::day4::{
+		assignments, fully_contains, gen, section_range, solve_part1, solve_part2, AssignmentError,
+		AssignmentPair, Coverage,
+	};
 
 	const EXAMPLE: &str = "2-4,6-8
 2-3,4-5
@@ -63,8 +222,8 @@ mod test {
 
 	#[test]
 	fn test_range_pair() {
-		let r1 = range_from_string("2-8".to_string());
-		let r2 = range_from_string("3-7".to_string());
+		let (_, r1) = section_range("2-8").unwrap();
+		let (_, r2) = section_range("3-7").unwrap();
 
 		assert!(fully_contains(r1, r2));
 	}
@@ -76,12 +235,71 @@ mod test {
 	/// so these seem like the most in need of reconsideration. In this example, there are `2` such pairs.
 	#[test]
 	fn example_part1() {
-		assert_eq!(2, solve_part1(&gen(EXAMPLE)));
+		assert_eq!(2, solve_part1(&gen(EXAMPLE).unwrap()));
 	}
 
 	/// So, in this example, the number of overlapping assignment pairs is `4`.
 	#[test]
 	fn example_part2() {
-		assert_eq!(4, solve_part2(&gen(EXAMPLE)));
+		assert_eq!(4, solve_part2(&gen(EXAMPLE).unwrap()));
+	}
+
+	#[test]
+	fn example_coverage() {
+		let coverage = Coverage::new(&assignments(&gen(EXAMPLE).unwrap()));
+
+		let depths = coverage.sections().map(|(_, d)| d).collect::<Vec<_>>();
+		assert_eq!(vec![4, 5, 7, 7, 8, 6, 4, 1], depths);
+		assert_eq!(8, coverage.max_depth());
+		assert_eq!(0, coverage.depth(1));
+		assert_eq!(0, coverage.depth(10));
+		assert!(coverage.gaps().is_empty());
+
+		// Elf 5 (`7-9`) is the only one covering section 9
+		assert_eq!(
+			vec![0, 1, 2, 3, 4, 6, 7, 8, 9, 10, 11],
+			coverage.redundant_elves()
+		);
+		// Elves 5 (`7-9`) and 10 (`2-6`) are enough to cover everything
+		assert_eq!(
+			vec![0, 1, 2, 3, 4, 6, 7, 8, 9, 11],
+			coverage.redundant_group()
+		);
+	}
+
+	#[test]
+	fn coverage_gaps() {
+		let coverage = Coverage::new(&[1..=2, 5..=5, 8..=9, 2..=3]);
+
+		assert_eq!(vec![4..=4, 6..=7], coverage.gaps());
+		assert_eq!(2, coverage.max_depth());
+		assert!(coverage.redundant_elves().is_empty());
+
+		assert_eq!(0, Coverage::new(&[]).sections().count());
+	}
+
+	#[test]
+	fn parse_errors() {
+		assert_eq!(
+			Ok(AssignmentPair(2..=4, 6..=8)),
+			AssignmentPair::from_line(1, "2-4,6-8")
+		);
+		assert_eq!(
+			Err(AssignmentError::Reversed {
+				line: 2,
+				start: 8,
+				end: 2
+			}),
+			gen("2-4,6-8\n3-7,8-2")
+		);
+		assert_eq!(
+			Err(AssignmentError::Malformed {
+				line: 3,
+				text: "2-4;6-8".to_string()
+			}),
+			gen("2-4,6-8\n2-3,4-5\n2-4;6-8")
+		);
+		assert!(gen("2-4,6-8,1-1").is_err());
+		assert!(gen("2-4,6-").is_err());
 	}
 }
@@ -2,14 +2,17 @@ use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete;
 use nom::character::complete::{
-	alpha1, char as complete_char, digit1, multispace1, newline, space1,
+	alpha1, char as complete_char, digit1, multispace1, newline, space0, space1,
 };
 use nom::multi::{many1, separated_list1};
 use nom::sequence::{delimited, preceded};
-use nom::IResult;
+use nom::{IResult, Parser};
+use std::fmt;
+use std::str::FromStr;
 use yaah::*;
 
-type Stacks = Vec<Vec<char>>;
+pub type Crate = String;
+pub type Stacks = Vec<Vec<Crate>>;
 
 #[aoc_generator(day5)]
 fn gen(input: &'static str) -> (Stacks, Vec<Move>) {
@@ -18,123 +21,398 @@ fn gen(input: &'static str) -> (Stacks, Vec<Move>) {
 }
 
 #[aoc(day5, part1)]
-fn solve_part1(input: &(Stacks, Vec<Move>)) -> String {
+fn solve_part1(input: &(Stacks, Vec<Move>)) -> Result<String, CraneError> {
 	let (stx, moves) = input;
 
-	let stacks = crane_single_items(stx.clone(), moves.clone());
+	let stacks = CrateMover9000.run(stx.clone(), moves)?;
 
-	stacks.iter().filter_map(|s| s.last()).collect()
+	Ok(CrateYard(stacks).tops())
 }
 
 #[aoc(day5, part2)]
-fn solve_part2(input: &(Stacks, Vec<Move>)) -> String {
+fn solve_part2(input: &(Stacks, Vec<Move>)) -> Result<String, CraneError> {
 	let (stx, moves) = input;
 
-	let stacks = crane_multiple_items(stx.clone(), moves.clone());
+	let stacks = CrateMover9001.run(stx.clone(), moves)?;
 
-	stacks.iter().filter_map(|s| s.last()).collect()
+	Ok(CrateYard(stacks).tops())
 }
 
-fn crane_single_items(mut stacks: Stacks, moves: Vec<Move>) -> Stacks {
-	#[cfg(feature = "debug")]
-	print_stacks(&stacks);
-	for Move { count, from, to } in moves.iter() {
-		for _ in 0..*count {
-			let c = stacks[*from as usize - 1].pop().unwrap();
-			stacks[*to as usize - 1].push(c);
+/// A crane moves crates between stacks, lifting up to `capacity` crates at a time.
+/// Each load keeps its order when it's set down on the destination stack.
+pub trait Crane {
+	fn capacity(&self) -> usize;
+
+	/// Applies a single move, leaving the stacks untouched if the move is invalid
+	fn apply(&self, stacks: &mut Stacks, mv: &Move) -> Result<(), MoveError> {
+		let from = stack_index(stacks, mv.from)?;
+		let to = stack_index(stacks, mv.to)?;
+		let available = stacks[from].len();
+		if available < mv.count as usize {
+			return Err(MoveError::NotEnoughCrates {
+				stack: mv.from,
+				count: mv.count,
+				available,
+			});
 		}
+
+		let mut remaining = mv.count as usize;
+		while remaining > 0 {
+			let lift = remaining.min(self.capacity().max(1));
+			let len = stacks[from].len();
+			let load = stacks[from].split_off(len - lift);
+			stacks[to].extend(load);
+			remaining -= lift;
+		}
+		Ok(())
+	}
+
+	/// Applies every move in order, stopping at the first invalid one
+	fn run(&self, mut stacks: Stacks, moves: &[Move]) -> Result<Stacks, CraneError> {
+		#[cfg(feature = "debug")]
+		println!("{}\n", CrateYard(stacks.clone()));
+		for (step, mv) in moves.iter().enumerate() {
+			self.apply(&mut stacks, mv).map_err(|error| CraneError {
+				step,
+				mv: *mv,
+				error,
+			})?;
+		}
+		#[cfg(feature = "debug")]
+		println!("{}\n", CrateYard(stacks.clone()));
+		Ok(stacks)
 	}
-	#[cfg(feature = "debug")]
-	print_stacks(&stacks);
-	stacks
 }
 
-fn crane_multiple_items(mut stacks: Stacks, moves: Vec<Move>) -> Stacks {
-	#[cfg(feature = "debug")]
-	print_stacks(&stacks);
-	for Move { count, from, to } in moves.iter() {
-		let mut load: Vec<char> = vec![];
-		for _ in 0..*count {
-			load.push(stacks[*from as usize - 1].pop().unwrap())
+/// Moves one crate at a time
+pub struct CrateMover9000;
+
+/// Moves any number of crates at once
+pub struct CrateMover9001;
+
+/// Moves up to `capacity` crates at once
+pub struct LimitedCrane {
+	pub capacity: usize,
+}
+
+impl Crane for CrateMover9000 {
+	fn capacity(&self) -> usize {
+		1
+	}
+}
+
+impl Crane for CrateMover9001 {
+	fn capacity(&self) -> usize {
+		usize::MAX
+	}
+}
+
+impl Crane for LimitedCrane {
+	fn capacity(&self) -> usize {
+		self.capacity
+	}
+}
+
+/// Stacks are numbered from 1 in the moves
+fn stack_index(stacks: &Stacks, stack: u8) -> Result<usize, MoveError> {
+	match stack as usize {
+		i @ 1.. if i <= stacks.len() => Ok(i - 1),
+		_ => Err(MoveError::NoSuchStack { stack }),
+	}
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MoveError {
+	NoSuchStack {
+		stack: u8,
+	},
+	NotEnoughCrates {
+		stack: u8,
+		count: u8,
+		available: usize,
+	},
+}
+
+impl fmt::Display for MoveError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			MoveError::NoSuchStack { stack } => write!(f, "there is no stack {stack}"),
+			MoveError::NotEnoughCrates {
+				stack,
+				count,
+				available,
+			} => write!(
+				f,
+				"can't move {count} crates from stack {stack} which only has {available}"
+			),
 		}
-		load.iter()
-			.rev()
-			.for_each(|c| stacks[*to as usize - 1].push(*c));
 	}
-	#[cfg(feature = "debug")]
-	print_stacks(&stacks);
-	stacks
 }
 
-#[cfg(feature = "debug")]
-fn print_stacks(stacks: &Stacks) {
-	let mut sorted_stacks = stacks.clone();
-	sorted_stacks.sort_by_key(|s| s.len());
-	let tallest = sorted_stacks.last().unwrap();
-	let stack_lines: Vec<String> = tallest
-		.iter()
-		.enumerate()
-		.map(|(idx, _)| {
-			stacks
-				.into_iter()
-				.map(move |stack| stack.get(idx))
-				.map(|cr| match cr {
-					Some(c) => format!("[{}]", c),
-					None => "   ".to_string(),
-				})
-				.collect::<Vec<String>>()
-				.join(" ")
+/// An invalid move along with its position in the list of moves
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CraneError {
+	pub step: usize,
+	pub mv: Move,
+	pub error: MoveError,
+}
+
+impl fmt::Display for CraneError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let Move { count, from, to } = self.mv;
+		write!(
+			f,
+			"move {} (`move {count} from {from} to {to}`): {}",
+			self.step + 1,
+			self.error
+		)
+	}
+}
+
+/// Every state of the yard as moves are applied by a crane, supporting undo and redo
+pub struct YardHistory<C: Crane> {
+	crane: C,
+	/// `states[n]` is the yard after the first `n` moves
+	states: Vec<Stacks>,
+	moves: Vec<Move>,
+	position: usize,
+}
+
+impl<C: Crane> YardHistory<C> {
+	pub fn new(crane: C, stacks: Stacks) -> Self {
+		YardHistory {
+			crane,
+			states: vec![stacks],
+			moves: vec![],
+			position: 0,
+		}
+	}
+
+	/// Applies the move to the current state, discarding anything that could have been redone
+	pub fn apply(&mut self, mv: Move) -> Result<(), MoveError> {
+		let mut stacks = self.stacks().clone();
+		self.crane.apply(&mut stacks, &mv)?;
+		self.states.truncate(self.position + 1);
+		self.moves.truncate(self.position);
+		self.states.push(stacks);
+		self.moves.push(mv);
+		self.position += 1;
+		Ok(())
+	}
+
+	pub fn apply_all(&mut self, moves: &[Move]) -> Result<(), CraneError> {
+		moves.iter().enumerate().try_for_each(|(step, mv)| {
+			self.apply(*mv).map_err(|error| CraneError {
+				step,
+				mv: *mv,
+				error,
+			})
 		})
-		.rev()
-		.collect();
+	}
 
-	for stack_line in stack_lines.iter() {
-		println!("{:?}", stack_line.as_str());
+	/// Steps back one move, returning the move that was undone
+	pub fn undo(&mut self) -> Option<Move> {
+		self.position = self.position.checked_sub(1)?;
+		Some(self.moves[self.position])
 	}
 
-	let label_line = stacks
-		.iter()
-		.enumerate()
-		.map(|(i, _)| format!(" { } ", i))
-		.collect::<Vec<String>>()
-		.join(" ");
+	/// Re-applies the last undone move
+	pub fn redo(&mut self) -> Option<Move> {
+		let mv = *self.moves.get(self.position)?;
+		self.position += 1;
+		Some(mv)
+	}
+
+	/// Jumps to the state after the first `n` moves, if they've been applied
+	pub fn goto(&mut self, n: usize) -> Option<&Stacks> {
+		if n < self.states.len() {
+			self.position = n;
+			Some(self.stacks())
+		} else {
+			None
+		}
+	}
+
+	/// Number of moves applied to reach the current state
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	pub fn stacks(&self) -> &Stacks {
+		&self.states[self.position]
+	}
+
+	/// The state after the first `n` moves
+	pub fn state(&self, n: usize) -> Option<&Stacks> {
+		self.states.get(n)
+	}
+
+	/// Moves applied to reach the current state
+	pub fn moves(&self) -> &[Move] {
+		&self.moves[..self.position]
+	}
+
+	/// First move after which the two histories no longer match, along with how they differ.
+	/// Only states up to the current positions count, so undone moves are ignored. When one
+	/// history runs further than the other, the shorter one is taken to stay at its last state,
+	/// so extra moves that change nothing aren't a divergence.
+	pub fn diverges_from<D: Crane>(
+		&self,
+		other: &YardHistory<D>,
+	) -> Option<(usize, Vec<StackDiff>)> {
+		let (left, right) = (
+			&self.states[..=self.position],
+			&other.states[..=other.position],
+		);
+		fn state(states: &[Stacks], n: usize) -> &Stacks {
+			states.get(n).unwrap_or(&states[states.len() - 1])
+		}
+		(0..left.len().max(right.len()))
+			.map(|n| (n, diff_stacks(state(left, n), state(right, n))))
+			.find(|(_, diff)| !diff.is_empty())
+	}
+}
+
+/// How a stack differs between two states: the crates above the shared bottom of the stack
+#[derive(Debug, PartialEq, Clone)]
+pub struct StackDiff {
+	/// Stack number, counting from 1 like the moves
+	pub stack: usize,
+	pub common: usize,
+	pub left: Vec<Crate>,
+	pub right: Vec<Crate>,
+}
+
+/// Differences between each pair of stacks, skipping the ones that match
+pub fn diff_stacks(left: &Stacks, right: &Stacks) -> Vec<StackDiff> {
+	let empty = vec![];
+	(0..left.len().max(right.len()))
+		.filter_map(|i| {
+			let l = left.get(i).unwrap_or(&empty);
+			let r = right.get(i).unwrap_or(&empty);
+			let common = l.iter().zip(r.iter()).take_while(|(a, b)| a == b).count();
+			(l != r).then(|| StackDiff {
+				stack: i + 1,
+				common,
+				left: l[common..].to_vec(),
+				right: r[common..].to_vec(),
+			})
+		})
+		.collect()
+}
+
+/// The stacks of crates, drawn the way the puzzle input draws them
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CrateYard(pub Stacks);
+
+impl CrateYard {
+	pub fn stacks(&self) -> &Stacks {
+		&self.0
+	}
+
+	/// The crate on top of each stack
+	pub fn tops(&self) -> String {
+		self.0
+			.iter()
+			.filter_map(|s| s.last())
+			.map(|c| c.as_str())
+			.collect()
+	}
+
+	/// Widest crate label, every slot in the drawing is padded to fit it
+	fn label_width(&self) -> usize {
+		self.0.iter().flatten().map(|c| c.len()).max().unwrap_or(1)
+	}
+}
+
+impl From<Stacks> for CrateYard {
+	fn from(stacks: Stacks) -> Self {
+		CrateYard(stacks)
+	}
+}
 
-	println!("{:?}\n", label_line);
+impl fmt::Display for CrateYard {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let slot = self.label_width() + 2;
+		let height = self.0.iter().map(|s| s.len()).max().unwrap_or(0);
+		for level in (0..height).rev() {
+			let line = self
+				.0
+				.iter()
+				.map(|stack| match stack.get(level) {
+					Some(c) => format!("{:<slot$}", format!("[{c}]")),
+					None => " ".repeat(slot),
+				})
+				.collect::<Vec<String>>()
+				.join(" ");
+			writeln!(f, "{line}")?;
+		}
+		let labels = (1..=self.0.len())
+			.map(|n| format!("{n:^slot$}"))
+			.collect::<Vec<String>>()
+			.join(" ");
+		write!(f, "{labels}")
+	}
 }
 
-///Nom parser function for parsing crate values
+impl FromStr for CrateYard {
+	type Err = nom::Err<nom::error::Error<String>>;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (_, stacks) = crate_yard(s).map_err(|e| e.to_owned())?;
+		Ok(CrateYard(stacks))
+	}
+}
+
+/// Nom parser function for parsing crate values
 /// - `   ` is an empty slot (`None`)
 /// - `[X]` is a Crate with the value `Some(X)`
 pub fn parse_crate(input: &str) -> IResult<&str, Option<&str>> {
-	let (input, c) = alt((
-		tag("   "),
-		delimited(complete_char('['), alpha1, complete_char(']')),
-	))(input)?;
+	crate_slot(1)(input)
+}
 
-	Ok((
-		input,
-		match c {
-			"   " => None,
-			v => Some(v),
-		},
-	))
+/// Parse a crate slot padded out to fit labels `width` characters long
+pub fn crate_slot(width: usize) -> impl Fn(&str) -> IResult<&str, Option<&str>> {
+	move |input| {
+		alt((
+			tag(" ".repeat(width + 2).as_str()).map(|_| None),
+			padded_crate(width).map(Some),
+		))(input)
+	}
+}
+
+/// Parse a `[X]` crate followed by enough spaces to pad it out to `width`
+fn padded_crate(width: usize) -> impl Fn(&str) -> IResult<&str, &str> {
+	move |input| {
+		let (input, label) = delimited(complete_char('['), alpha1, complete_char(']'))(input)?;
+		let (input, _) = tag(" ".repeat(width.saturating_sub(label.len())).as_str())(input)?;
+		Ok((input, label))
+	}
 }
 
 /// Parse Crate lines
+pub fn crate_line(width: usize) -> impl Fn(&str) -> IResult<&str, Vec<Option<&str>>> {
+	move |input| separated_list1(tag(" "), crate_slot(width))(input)
+}
 
-pub fn crate_line(input: &str) -> IResult<&str, Vec<Option<&str>>> {
-	let (input, crates) = separated_list1(tag(" "), parse_crate)(input)?;
-	Ok((input, crates))
+pub fn crate_lines(width: usize) -> impl Fn(&str) -> IResult<&str, Vec<Vec<Option<&str>>>> {
+	move |input| separated_list1(newline, crate_line(width))(input)
 }
 
-pub fn crate_lines(input: &str) -> IResult<&str, Vec<Vec<Option<&str>>>> {
-	let (input, crates) = separated_list1(newline, crate_line)(input)?;
-	Ok((input, crates))
+/// Widest crate label in the drawing at the start of the input
+fn label_width(input: &str) -> usize {
+	input
+		.lines()
+		.take_while(|line| line.contains('['))
+		.flat_map(|line| line.split('[').skip(1))
+		.filter_map(|label| label.find(']'))
+		.max()
+		.unwrap_or(1)
 }
 
 /// Parse Crates from input lines. These lines come in top down, so they need to be rotated.
 pub fn crate_stacks(input: &str) -> IResult<&str, Stacks> {
-	let (input, crate_lines) = crate_lines(input)?;
+	let (input, crate_lines) = crate_lines(label_width(input))(input)?;
 	let stacks: Stacks = crate_lines
 		.first()
 		.unwrap()
@@ -143,20 +421,31 @@ pub fn crate_stacks(input: &str) -> IResult<&str, Stacks> {
 		.map(|(i, _)| {
 			crate_lines
 				.iter()
-				.filter_map(|cl| cl[i])
-				.filter_map(|s| s.chars().next())
+				.filter_map(|cl| cl.get(i).copied().flatten())
+				.map(|s| s.to_string())
 				.rev()
-				.collect::<Vec<char>>()
+				.collect::<Vec<Crate>>()
 		})
 		.collect();
 	Ok((input, stacks))
 }
 
+/// Parse the crate drawing along with its numbered footer
+pub fn crate_yard(input: &str) -> IResult<&str, Stacks> {
+	let (input, stacks) = crate_stacks(input)?;
+
+	let (input, _) = newline(input)?;
+	let (input, _) = many1(preceded(space1, digit1))(input)?;
+	let (input, _) = space0(input)?;
+
+	Ok((input, stacks))
+}
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Move {
-	count: u8,
-	from: u8,
-	to: u8,
+	pub count: u8,
+	pub from: u8,
+	pub to: u8,
 }
 
 /// Parsing move line like `move 1 from 2 to 1`
@@ -178,10 +467,7 @@ pub fn moves(input: &str) -> IResult<&str, Vec<Move>> {
 
 /// Parsing input with nom
 fn parse_input(input: &str) -> IResult<&str, (Stacks, Vec<Move>)> {
-	let (input, stacks) = crate_stacks(input)?;
-
-	let (input, _) = newline(input)?;
-	let (input, _) = many1(preceded(space1, digit1))(input)?;
+	let (input, stacks) = crate_yard(input)?;
 	let (input, _) = multispace1(input)?;
 
 	let (input, moves) = moves(input)?;
@@ -191,7 +477,15 @@ fn parse_input(input: &str) -> IResult<&str, (Stacks, Vec<Move>)> {
 
 #[cfg(test)]
 mod test {
-	use crate::day5::{gen, parse_crate, parse_move, solve_part1, solve_part2, Move};
+	use crate::day5::{
+		crate_slot, gen, parse_crate, parse_move, solve_part1, solve_part2, Crane, CraneError,
+		Crate, CrateMover9000, CrateMover9001, CrateYard, LimitedCrane, Move, MoveError, StackDiff,
+		YardHistory,
+	};
+
+	fn crates(labels: &str) -> Vec<Crate> {
+		labels.chars().map(String::from).collect()
+	}
 
 	const EXAMPLE: &str = concat!(
 		"    [D]    \n",
@@ -209,7 +503,7 @@ mod test {
 	fn example() {
 		let (stacks, moves) = gen(EXAMPLE);
 
-		let expected_stacks = vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']];
+		let expected_stacks = vec![crates("ZN"), crates("MCD"), crates("P")];
 		assert_eq!(stacks, expected_stacks);
 
 		let expected_moves = vec![
@@ -241,6 +535,9 @@ mod test {
 	fn test_crate_parsing() {
 		assert_eq!(parse_crate("   "), Ok(("", None)));
 		assert_eq!(parse_crate("[A]"), Ok(("", Some("A"))));
+		assert_eq!(crate_slot(2)("[A] "), Ok(("", Some("A"))));
+		assert_eq!(crate_slot(2)("[AB]"), Ok(("", Some("AB"))));
+		assert_eq!(crate_slot(2)("    "), Ok(("", None)));
 	}
 
 	#[test]
@@ -263,11 +560,164 @@ mod test {
 	/// so you should combine these together and give the Elves the message `CMZ`.
 	#[test]
 	fn part1() {
-		assert_eq!(solve_part1(&gen(EXAMPLE)), "CMZ")
+		assert_eq!(solve_part1(&gen(EXAMPLE)), Ok("CMZ".to_string()))
 	}
 
 	#[test]
 	fn part2() {
-		assert_eq!(solve_part2(&gen(EXAMPLE)), "MCD")
+		assert_eq!(solve_part2(&gen(EXAMPLE)), Ok("MCD".to_string()))
+	}
+
+	#[test]
+	fn limited_crane() {
+		let stacks = vec![crates("ABCDE"), vec![]];
+		let mv = Move {
+			count: 5,
+			from: 1,
+			to: 2,
+		};
+
+		let moved = |crane: &dyn Crane| crane.run(stacks.clone(), &[mv]).unwrap()[1].clone();
+		assert_eq!(crates("EDCBA"), moved(&CrateMover9000));
+		assert_eq!(crates("ABCDE"), moved(&CrateMover9001));
+		assert_eq!(crates("DEBCA"), moved(&LimitedCrane { capacity: 2 }));
+	}
+
+	#[test]
+	fn invalid_moves() {
+		let (stacks, _) = gen(EXAMPLE);
+		let mv = |count, from, to| Move { count, from, to };
+
+		let mut untouched = stacks.clone();
+		assert_eq!(
+			Err(MoveError::NotEnoughCrates {
+				stack: 3,
+				count: 2,
+				available: 1
+			}),
+			CrateMover9000.apply(&mut untouched, &mv(2, 3, 1))
+		);
+		assert_eq!(stacks, untouched);
+
+		assert_eq!(
+			Err(MoveError::NoSuchStack { stack: 4 }),
+			CrateMover9001.apply(&mut untouched, &mv(1, 1, 4))
+		);
+		assert_eq!(
+			Err(MoveError::NoSuchStack { stack: 0 }),
+			CrateMover9001.apply(&mut untouched, &mv(1, 0, 1))
+		);
+
+		assert_eq!(
+			Err(CraneError {
+				step: 1,
+				mv: mv(3, 1, 2),
+				error: MoveError::NotEnoughCrates {
+					stack: 1,
+					count: 3,
+					available: 1
+				}
+			}),
+			CrateMover9000.run(stacks, &[mv(1, 1, 3), mv(3, 1, 2)])
+		);
+	}
+
+	#[test]
+	fn history() {
+		let (stacks, moves) = gen(EXAMPLE);
+		let mut history = YardHistory::new(CrateMover9000, stacks.clone());
+		history.apply_all(&moves).unwrap();
+
+		assert_eq!(4, history.position());
+		assert_eq!(
+			&vec![crates("C"), crates("M"), crates("PDNZ")],
+			history.stacks()
+		);
+
+		assert_eq!(Some(moves[3]), history.undo());
+		assert_eq!(Some(moves[2]), history.undo());
+		assert_eq!(&moves[..2], history.moves());
+		assert_eq!(Some(moves[2]), history.redo());
+		assert_eq!(3, history.position());
+
+		assert_eq!(Some(&stacks), history.goto(0));
+		assert_eq!(None, history.undo());
+		assert_eq!(None, history.goto(5));
+
+		history.goto(1);
+		history.apply(moves[3]).unwrap();
+		assert_eq!(2, history.position());
+		assert_eq!(None, history.redo());
+		assert_eq!(&[moves[0], moves[3]], history.moves());
+	}
+
+	#[test]
+	fn crane_divergence() {
+		let (stacks, moves) = gen(EXAMPLE);
+		let mut single = YardHistory::new(CrateMover9000, stacks.clone());
+		let mut multiple = YardHistory::new(CrateMover9001, stacks);
+		single.apply_all(&moves).unwrap();
+		multiple.apply_all(&moves).unwrap();
+
+		// Moving a single crate is the same for both cranes, `move 3 from 1 to 3` is not.
+		assert_eq!(
+			Some((
+				2,
+				vec![StackDiff {
+					stack: 3,
+					common: 1,
+					left: crates("DNZ"),
+					right: crates("ZND"),
+				}]
+			)),
+			single.diverges_from(&multiple)
+		);
+
+		// Undone moves don't count, until one history moves on past the other
+		(0..3).for_each(|_| {
+			single.undo();
+			multiple.undo();
+		});
+		assert_eq!(None, single.diverges_from(&multiple));
+		multiple.redo();
+		assert_eq!(Some(2), multiple.diverges_from(&single).map(|(n, _)| n));
+		assert_eq!(Some(2), single.diverges_from(&multiple).map(|(n, _)| n));
+
+		// Running further with a move that changes nothing isn't a divergence
+		multiple.undo();
+		let idle = Move {
+			count: 1,
+			from: 1,
+			to: 1,
+		};
+		multiple.apply(idle).unwrap();
+		assert_eq!(None, single.diverges_from(&multiple));
+		assert_eq!(None, multiple.diverges_from(&single));
+	}
+
+	#[test]
+	fn yard_drawing() {
+		let drawing = EXAMPLE.split("\n\n").next().unwrap();
+		let yard: CrateYard = drawing.parse().unwrap();
+
+		assert_eq!(
+			&vec![crates("ZN"), crates("MCD"), crates("P")],
+			yard.stacks()
+		);
+		assert_eq!("NDP", yard.tops());
+		assert_eq!(drawing, yard.to_string());
+	}
+
+	#[test]
+	fn multi_character_labels() {
+		let yard = CrateYard(vec![
+			vec!["AB".to_string(), "C".to_string()],
+			vec![],
+			vec!["DE".to_string()],
+		]);
+		let drawing = concat!("[C]           \n", "[AB]      [DE]\n", " 1    2    3  ");
+
+		assert_eq!(drawing, yard.to_string());
+		assert_eq!(Ok(yard), drawing.parse());
 	}
 }
@@ -1,54 +1,127 @@
-use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::io::{self, BufReader, Read};
 use yaah::*;
 
+pub const START_OF_PACKET: usize = 4;
+pub const START_OF_MESSAGE: usize = 14;
+
 #[aoc(day6, part1)]
-fn solve_part1(input: &'static str) -> u32 {
-	let range = 0..(input.len() - 4);
-	range
-		.into_iter()
-		.find_map(
-			|i| match &input[i..(i + 4)].chars().collect::<HashSet<char>>().len() {
-				4 => Some(i as u32 + 4),
-				_ => None,
-			},
-		)
-		.unwrap()
+fn solve_part1(input: &'static str) -> Option<usize> {
+	find_marker(input.as_bytes(), START_OF_PACKET)
 }
 
 #[aoc(day6, part2)]
-fn solve_part2(input: &'static str) -> u32 {
-	let range = 0..(input.len() - 14);
-	range
-		.into_iter()
-		.find_map(
-			|i| match &input[i..(i + 14)].chars().collect::<HashSet<char>>().len() {
-				14 => Some(i as u32 + 14),
-				_ => None,
-			},
-		)
-		.unwrap()
+fn solve_part2(input: &'static str) -> Option<usize> {
+	find_marker(input.as_bytes(), START_OF_MESSAGE)
+}
+
+/// Number of characters processed once the first `window` distinct characters have been seen
+pub fn find_marker(input: &[u8], window: usize) -> Option<usize> {
+	markers(input, window).next()
+}
+
+/// Every position where the last `window` characters are all distinct
+pub fn markers(input: &[u8], window: usize) -> impl Iterator<Item = usize> + '_ {
+	let mut detector = MarkerDetector::new(window);
+	input.iter().filter_map(move |b| detector.push(*b))
+}
+
+/// Same as [find_marker] but reading the datastream from any reader
+pub fn find_marker_in<R: Read>(reader: R, window: usize) -> io::Result<Option<usize>> {
+	let mut detector = MarkerDetector::new(window);
+	for byte in BufReader::new(reader).bytes() {
+		if let Some(position) = detector.push(byte?) {
+			return Ok(Some(position));
+		}
+	}
+	Ok(None)
+}
+
+/// Rolling count of the bytes in the window, so each byte is handled in constant time
+pub struct MarkerDetector {
+	window: usize,
+	buffer: VecDeque<u8>,
+	counts: [usize; 256],
+	duplicates: usize,
+	position: usize,
+}
+
+impl MarkerDetector {
+	/// A window of `0` never matches
+	pub fn new(window: usize) -> Self {
+		MarkerDetector {
+			window,
+			buffer: VecDeque::with_capacity(window + 1),
+			counts: [0; 256],
+			duplicates: 0,
+			position: 0,
+		}
+	}
+
+	/// Adds the next byte, returning the position if it completes a marker
+	pub fn push(&mut self, byte: u8) -> Option<usize> {
+		self.position += 1;
+		self.buffer.push_back(byte);
+		self.counts[byte as usize] += 1;
+		if self.counts[byte as usize] == 2 {
+			self.duplicates += 1;
+		}
+		if self.buffer.len() > self.window {
+			let old = self.buffer.pop_front().unwrap() as usize;
+			self.counts[old] -= 1;
+			if self.counts[old] == 1 {
+				self.duplicates -= 1;
+			}
+		}
+
+		(self.window > 0 && self.buffer.len() == self.window && self.duplicates == 0)
+			.then_some(self.position)
+	}
 }
 
 #[cfg(test)]
 mod test {
-	use crate::day6::{solve_part1, solve_part2};
+	use crate::day6::{find_marker, find_marker_in, markers, solve_part1, solve_part2};
 
 	#[test]
 	fn examples() {
-		assert_eq!(7, solve_part1("mjqjpqmgbljsphdztnvjfqwrcgsmlb"));
-		assert_eq!(5, solve_part1("bvwbjplbgvbhsrlpgdmjqwftvncz"));
-		assert_eq!(6, solve_part1("nppdvjthqldpwncqszvftbrmjlhg"));
-		assert_eq!(10, solve_part1("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg"));
-		assert_eq!(11, solve_part1("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw"));
+		assert_eq!(Some(7), solve_part1("mjqjpqmgbljsphdztnvjfqwrcgsmlb"));
+		assert_eq!(Some(5), solve_part1("bvwbjplbgvbhsrlpgdmjqwftvncz"));
+		assert_eq!(Some(6), solve_part1("nppdvjthqldpwncqszvftbrmjlhg"));
+		assert_eq!(Some(10), solve_part1("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg"));
+		assert_eq!(Some(11), solve_part1("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw"));
 	}
 
 	#[test]
 	fn examples_part2() {
-		assert_eq!(19, solve_part2("mjqjpqmgbljsphdztnvjfqwrcgsmlb"));
-		assert_eq!(23, solve_part2("bvwbjplbgvbhsrlpgdmjqwftvncz"));
-		assert_eq!(23, solve_part2("nppdvjthqldpwncqszvftbrmjlhg"));
-		assert_eq!(29, solve_part2("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg"));
-		assert_eq!(26, solve_part2("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw"));
+		assert_eq!(Some(19), solve_part2("mjqjpqmgbljsphdztnvjfqwrcgsmlb"));
+		assert_eq!(Some(23), solve_part2("bvwbjplbgvbhsrlpgdmjqwftvncz"));
+		assert_eq!(Some(23), solve_part2("nppdvjthqldpwncqszvftbrmjlhg"));
+		assert_eq!(Some(29), solve_part2("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg"));
+		assert_eq!(Some(26), solve_part2("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw"));
+	}
+
+	#[test]
+	fn short_input() {
+		assert_eq!(None, solve_part1("abc"));
+		assert_eq!(None, solve_part2("mjqjpqmgbljsph"));
+		assert_eq!(None, find_marker(b"", 4));
+		assert_eq!(None, find_marker(b"abcd", 0));
+		assert_eq!(Some(1), find_marker(b"aab", 1));
+	}
+
+	#[test]
+	fn every_marker() {
+		assert_eq!(vec![3, 4, 5], markers(b"abcab", 3).collect::<Vec<_>>());
+		assert_eq!(vec![4, 9], markers(b"abcdddefg", 4).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn reader() {
+		let stream = "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg".as_bytes();
+		assert_eq!(Some(10), find_marker_in(stream, 4).unwrap());
+		assert_eq!(Some(29), find_marker_in(stream, 14).unwrap());
+		assert_eq!(None, find_marker_in(&stream[..8], 14).unwrap());
 	}
 }
 // mjqjpqmgbljsphdztnvjfqwrcgsmlb
@@ -1,138 +1,830 @@
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete;
-use nom::character::complete::{alphanumeric1, line_ending, multispace1, newline, not_line_ending};
-use nom::multi::separated_list1;
-use nom::sequence::{preceded, tuple};
-use nom::IResult;
+use nom::character::complete::{line_ending, not_line_ending, space0, space1};
+use nom::combinator::{eof, peek, verify};
+use nom::multi::{many0, separated_list1};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::{IResult, Parser};
+use serde_json::json;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
 use yaah::*;
 
-type FileSystem = HashMap<String, u32>;
-
 #[aoc(day7, part1)]
 fn solve_part1(input: &'static str) -> u32 {
-	let (_, commands) = parse_terminal(input).unwrap();
-
-	let fs: FileSystem = build_fs(commands);
-	let predicate = |size: &u32| *size <= 100000;
-	let undersize: Vec<u32> = filter_dir_size(fs, &predicate);
+	let (fs, _warnings) = load(input);
+	#[cfg(feature = "debug")]
+	_warnings.iter().for_each(|warning| println!("{warning}"));
 
-	undersize.into_iter().sum()
+	fs.dirs()
+		.map(|dir| fs.size(dir))
+		.filter(|size| *size <= 100000)
+		.sum()
 }
 
 #[aoc(day7, part2)]
-fn solve_part2(input: &'static str) -> u32 {
-	let (_, commands) = parse_terminal(input).unwrap();
-	let fs: FileSystem = build_fs(commands);
-
-	let total_disk_space: u32 = 70000000;
-	let required_free_space: u32 = 30000000;
-	let total_used: u32 = fs.keys().filter_map(|k| fs.get(k)).sum();
-	let unused_space = total_disk_space - total_used;
-	let min_deleted = required_free_space - unused_space;
-
-	let predicate = |size: &u32| *size >= min_deleted;
-	let undersize: Vec<u32> = filter_dir_size(fs, &predicate);
-
-	undersize.into_iter().min().unwrap()
-}
-
-fn build_fs(commands: Vec<Command>) -> FileSystem {
-	let mut file_system: FileSystem = FileSystem::new();
-	let mut cwd = vec!["/".to_string()];
-	for command in commands {
-		match command {
-			Command::ChangeDirectory { target } => match String::from(target).as_str() {
-				"/" => cwd.clear(),
-				".." => {
-					cwd.pop();
+fn solve_part2(input: &'static str) -> Result<u32, CleanupError> {
+	let (fs, _warnings) = load(input);
+	#[cfg(feature = "debug")]
+	_warnings.iter().for_each(|warning| println!("{warning}"));
+
+	let plan = CleanupPlanner::default().plan(&fs)?;
+	Ok(plan.smallest_dir.map_or(0, |dir| fs.size(dir)))
+}
+
+/// Parses and replays a whole transcript, anything the parser couldn't reach is reported as a warning
+pub fn load(input: &str) -> (FileSystem, Vec<Warning>) {
+	let (tail, commands) = match parse_terminal(input) {
+		Ok(parsed) => parsed,
+		Err(_) => (input, vec![]),
+	};
+	let (fs, mut warnings) = FileSystem::replay(commands);
+	if !tail.trim().is_empty() {
+		warnings.push(Warning::TrailingInput {
+			line: input[..input.len() - tail.len()].lines().count().max(1),
+			text: tail.to_string(),
+		});
+	}
+	(fs, warnings)
+}
+
+/// Index of a node in the [FileSystem] arena
+pub type NodeId = usize;
+
+#[derive(Debug, PartialEq)]
+pub enum NodeKind {
+	Dir { children: Vec<NodeId> },
+	File,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Node {
+	pub name: String,
+	pub parent: Option<NodeId>,
+	/// File size, or the total size of everything below a directory
+	pub size: u32,
+	pub kind: NodeKind,
+}
+
+impl Node {
+	pub fn is_dir(&self) -> bool {
+		matches!(self.kind, NodeKind::Dir { .. })
+	}
+}
+
+/// Directory tree stored in an arena, with directory sizes kept up to date as files are added
+#[derive(Debug, PartialEq)]
+pub struct FileSystem {
+	nodes: Vec<Node>,
+}
+
+impl Default for FileSystem {
+	fn default() -> Self {
+		FileSystem {
+			nodes: vec![Node {
+				name: "/".to_string(),
+				parent: None,
+				size: 0,
+				kind: NodeKind::Dir { children: vec![] },
+			}],
+		}
+	}
+}
+
+impl FileSystem {
+	pub const ROOT: NodeId = 0;
+
+	/// Replays the terminal session to reconstruct the directory tree
+	pub fn from_commands(commands: Vec<Command>) -> Self {
+		let (fs, _) = FileSystem::replay(commands);
+		fs
+	}
+
+	/// Replays the terminal session, noting anything inconsistent instead of giving up
+	pub fn replay(commands: Vec<Command>) -> (Self, Vec<Warning>) {
+		let mut fs = FileSystem::default();
+		let mut warnings = vec![];
+		let mut listed = HashSet::new();
+		let mut cwd = FileSystem::ROOT;
+		// Each command and each line of its output takes up a line of the transcript
+		let mut line = 0;
+		for command in commands {
+			line += 1;
+			match command {
+				Command::ChangeDirectory { target } => {
+					cwd = fs.change_directory(cwd, &target, &mut warnings);
+				}
+				Command::List { entries } => {
+					if !listed.insert(cwd) {
+						fs.check_relisting(cwd, &entries, &mut warnings);
+					}
+					for entry in entries {
+						line += 1;
+						fs.list_entry(cwd, entry, line, &mut warnings);
+					}
+				}
+				Command::Unknown { command, output } => {
+					warnings.push(Warning::UnknownCommand {
+						command: command.to_string(),
+					});
+					line += output;
+				}
+				Command::Unparsed { text } => unparsed(text, line, &mut warnings),
+			}
+		}
+		(fs, warnings)
+	}
+
+	/// Follows a `cd` target like `..`, `a/e` or `/d`, creating directories as needed
+	fn change_directory(
+		&mut self,
+		cwd: NodeId,
+		target: &str,
+		warnings: &mut Vec<Warning>,
+	) -> NodeId {
+		let start = match target.starts_with('/') {
+			true => FileSystem::ROOT,
+			false => cwd,
+		};
+		target
+			.split('/')
+			.filter(|part| !part.is_empty())
+			.fold(start, |dir, part| match part {
+				"." => dir,
+				".." => self.parent(dir).unwrap_or(FileSystem::ROOT),
+				name => match self.child(dir, name) {
+					Some(file) if !self.node(file).is_dir() => {
+						warnings.push(Warning::NotADirectory {
+							path: self.path(file),
+						});
+						dir
+					}
+					_ => self.add_dir(dir, name),
+				},
+			})
+	}
+
+	fn list_entry(
+		&mut self,
+		cwd: NodeId,
+		entry: ListEntry,
+		line: usize,
+		warnings: &mut Vec<Warning>,
+	) {
+		match entry {
+			ListEntry::Unparsed { text } => unparsed(text, line, warnings),
+			ListEntry::Dir { name } => match self.child(cwd, &name) {
+				Some(file) if !self.node(file).is_dir() => warnings.push(Warning::KindChanged {
+					path: self.path(file),
+				}),
+				_ => {
+					self.add_dir(cwd, &name);
 				}
-				name => cwd.push(name.to_string()),
 			},
-			Command::List { entries } => {
-				let path = build_path(&cwd);
-				let size: u32 = entries
-					.into_iter()
-					.map(|e| match e {
-						ListEntry::Dir { name: _ } => 0,
-						ListEntry::File { size, name: _ } => size,
-					})
-					.sum();
-				file_system.insert(path, size);
+			ListEntry::File { size, name } => match self.child(cwd, &name) {
+				Some(dir) if self.node(dir).is_dir() => warnings.push(Warning::KindChanged {
+					path: self.path(dir),
+				}),
+				Some(file) if self.size(file) != size => {
+					warnings.push(Warning::SizeChanged {
+						path: self.path(file),
+						was: self.size(file),
+						now: size,
+					});
+					self.resize_file(file, size);
+				}
+				Some(_) => {}
+				None => {
+					self.add_file(cwd, &name, size);
+				}
+			},
+		}
+	}
+
+	/// Anything listed in the directory before that's missing from this listing
+	fn check_relisting(&self, dir: NodeId, entries: &[ListEntry], warnings: &mut Vec<Warning>) {
+		let names: HashSet<&str> = entries
+			.iter()
+			.filter_map(|entry| match entry {
+				ListEntry::Dir { name } | ListEntry::File { name, .. } => Some(name.as_ref()),
+				ListEntry::Unparsed { .. } => None,
+			})
+			.collect();
+		warnings.extend(
+			self.children(dir)
+				.iter()
+				.filter(|child| !names.contains(self.name(**child)))
+				.map(|child| Warning::Missing {
+					path: self.path(*child),
+				}),
+		);
+	}
+
+	pub fn node(&self, id: NodeId) -> &Node {
+		&self.nodes[id]
+	}
+
+	pub fn name(&self, id: NodeId) -> &str {
+		&self.nodes[id].name
+	}
+
+	pub fn size(&self, id: NodeId) -> u32 {
+		self.nodes[id].size
+	}
+
+	pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+		self.nodes[id].parent
+	}
+
+	/// Contents of a directory in the order they were listed, files have none
+	pub fn children(&self, id: NodeId) -> &[NodeId] {
+		match &self.nodes[id].kind {
+			NodeKind::Dir { children } => children,
+			NodeKind::File => &[],
+		}
+	}
+
+	pub fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+		self.children(dir)
+			.iter()
+			.copied()
+			.find(|id| self.nodes[*id].name == name)
+	}
+
+	/// Finds a node by its path from the root, like `/a/e` or `/d/d.log`
+	pub fn lookup(&self, path: &str) -> Option<NodeId> {
+		path.split('/')
+			.filter(|part| !part.is_empty())
+			.try_fold(FileSystem::ROOT, |dir, name| self.child(dir, name))
+	}
+
+	/// Full path of a node, directories end with `/`
+	pub fn path(&self, id: NodeId) -> String {
+		let mut names = vec![];
+		let mut node = Some(id);
+		while let Some(current) = node.filter(|n| *n != FileSystem::ROOT) {
+			names.push(self.name(current));
+			node = self.parent(current);
+		}
+		names.reverse();
+		match (names.is_empty(), self.nodes[id].is_dir()) {
+			(true, _) => "/".to_string(),
+			(false, true) => format!("/{}/", names.join("/")),
+			(false, false) => format!("/{}", names.join("/")),
+		}
+	}
+
+	/// Every node depth first in listing order, along with its depth below the root
+	pub fn walk(&self) -> impl Iterator<Item = (NodeId, usize)> + '_ {
+		let mut stack = vec![(FileSystem::ROOT, 0)];
+		std::iter::from_fn(move || {
+			let (id, depth) = stack.pop()?;
+			stack.extend(self.children(id).iter().rev().map(|c| (*c, depth + 1)));
+			Some((id, depth))
+		})
+	}
+
+	/// Whether one of the nodes is inside the other, or they're the same node
+	pub fn is_nested(&self, a: NodeId, b: NodeId) -> bool {
+		self.is_within(a, b) || self.is_within(b, a)
+	}
+
+	/// Whether `node` is `dir` or somewhere below it
+	pub fn is_within(&self, node: NodeId, dir: NodeId) -> bool {
+		let mut current = Some(node);
+		while let Some(id) = current {
+			if id == dir {
+				return true;
 			}
+			current = self.parent(id);
+		}
+		false
+	}
+
+	/// Every directory, starting with the root
+	pub fn dirs(&self) -> impl Iterator<Item = NodeId> + '_ {
+		self.walk()
+			.map(|(id, _)| id)
+			.filter(|id| self.nodes[*id].is_dir())
+	}
+
+	/// Gets or creates the directory `name` within `parent`
+	pub fn add_dir(&mut self, parent: NodeId, name: &str) -> NodeId {
+		self.child(parent, name)
+			.unwrap_or_else(|| self.insert(parent, name, 0, NodeKind::Dir { children: vec![] }))
+	}
+
+	/// Gets or creates the file `name` within `parent`, adding its size to every directory above it
+	pub fn add_file(&mut self, parent: NodeId, name: &str, size: u32) -> NodeId {
+		if let Some(existing) = self.child(parent, name) {
+			return existing;
+		}
+		let id = self.insert(parent, name, size, NodeKind::File);
+		let mut dir = Some(parent);
+		while let Some(current) = dir {
+			self.nodes[current].size += size;
+			dir = self.parent(current);
+		}
+		id
+	}
+
+	/// Changes a file's size, keeping the directory totals above it in step
+	fn resize_file(&mut self, file: NodeId, size: u32) {
+		let old = self.size(file);
+		let mut node = Some(file);
+		while let Some(current) = node {
+			self.nodes[current].size = self.nodes[current].size - old + size;
+			node = self.parent(current);
+		}
+	}
+
+	fn insert(&mut self, parent: NodeId, name: &str, size: u32, kind: NodeKind) -> NodeId {
+		let id = self.nodes.len();
+		self.nodes.push(Node {
+			name: name.to_string(),
+			parent: Some(parent),
+			size,
+			kind,
+		});
+		if let NodeKind::Dir { children } = &mut self.nodes[parent].kind {
+			children.push(id);
+		}
+		id
+	}
+}
+
+/// Works out which directories to delete to free up enough space for an update
+#[derive(Debug, Clone, Copy)]
+pub struct CleanupPlanner {
+	pub disk_size: u32,
+	pub required_free_space: u32,
+}
+
+impl Default for CleanupPlanner {
+	fn default() -> Self {
+		CleanupPlanner {
+			disk_size: 70000000,
+			required_free_space: 30000000,
 		}
 	}
+}
 
-	file_system
+#[derive(Debug, PartialEq, Clone)]
+pub struct CleanupPlan {
+	/// How much space has to be freed, `0` if there's already enough
+	pub needed: u32,
+	/// Smallest directory that frees enough space on its own, which may be `/`
+	pub smallest_dir: Option<NodeId>,
+	/// Fewest directories, none inside another, that free enough space.
+	/// Of all the sets that size, this one deletes the least.
+	/// Only `/` when nothing short of deleting everything will do.
+	pub minimal_set: Vec<NodeId>,
+	/// Total size of the minimal set
+	pub freed: u32,
 }
 
-fn filter_dir_size(fs: FileSystem, size_predicate: &dyn Fn(&u32) -> bool) -> Vec<u32> {
-	fs.keys()
-		.filter_map(|key| {
-			fs.keys()
-				.filter(|k2| k2.starts_with(key))
-				.map(|k| fs.get(k))
-				.sum()
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CleanupError {
+	/// Even an empty disk wouldn't have enough free space
+	DiskTooSmall {
+		disk_size: u32,
+		required_free_space: u32,
+	},
+}
+
+impl fmt::Display for CleanupError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			CleanupError::DiskTooSmall {
+				disk_size,
+				required_free_space,
+			} => write!(
+				f,
+				"a disk of {disk_size} can never have {required_free_space} free"
+			),
+		}
+	}
+}
+
+impl CleanupPlanner {
+	/// Space that has to be freed, the tree may be larger than the disk
+	pub fn space_needed(&self, fs: &FileSystem) -> Result<u32, CleanupError> {
+		if self.required_free_space > self.disk_size {
+			return Err(CleanupError::DiskTooSmall {
+				disk_size: self.disk_size,
+				required_free_space: self.required_free_space,
+			});
+		}
+		let used = fs.size(FileSystem::ROOT) as u64;
+		let needed = (self.required_free_space as u64 + used).saturating_sub(self.disk_size as u64);
+		Ok(needed as u32)
+	}
+
+	/// Plans which directories to delete.
+	///
+	/// The minimal set is found by branch and bound over the directories outside `/`, so the
+	/// worst case grows with `dirs` choose `k`, where `k` is the size of the set.
+	pub fn plan(&self, fs: &FileSystem) -> Result<CleanupPlan, CleanupError> {
+		let needed = self.space_needed(fs)?;
+		if needed == 0 {
+			return Ok(CleanupPlan {
+				needed,
+				smallest_dir: None,
+				minimal_set: vec![],
+				freed: 0,
+			});
+		}
+
+		let mut candidates: Vec<NodeId> = fs.dirs().collect();
+		candidates.sort_by_key(|dir| Reverse(fs.size(*dir)));
+		let smallest_dir = candidates
+			.iter()
+			.rev()
+			.find(|dir| fs.size(**dir) >= needed)
+			.copied();
+		candidates.retain(|dir| *dir != FileSystem::ROOT);
+
+		// The largest top level directories are the fewest that could free enough
+		let mut top_level: Vec<u32> = fs
+			.children(FileSystem::ROOT)
+			.iter()
+			.filter(|c| fs.node(**c).is_dir())
+			.map(|c| fs.size(*c))
+			.collect();
+		top_level.sort_by_key(|size| Reverse(*size));
+		let fewest = top_level
+			.iter()
+			.scan(0, |freed, size| {
+				*freed += size;
+				Some(*freed)
+			})
+			.position(|freed| freed >= needed);
+		// Files directly in `/` are needed too, only deleting everything frees enough
+		let Some(fewest) = fewest.map(|position| position + 1) else {
+			return Ok(CleanupPlan {
+				needed,
+				smallest_dir,
+				minimal_set: vec![FileSystem::ROOT],
+				freed: fs.size(FileSystem::ROOT),
+			});
+		};
+
+		let mut search = SetSearch {
+			fs,
+			candidates: &candidates,
+			needed,
+			chosen: vec![],
+			best: None,
+		};
+		search.search(0, fewest, 0);
+		let (freed, mut minimal_set) = search.best.unwrap();
+		minimal_set.sort();
+
+		Ok(CleanupPlan {
+			needed,
+			smallest_dir,
+			minimal_set,
+			freed,
 		})
-		.filter(size_predicate)
-		.collect()
+	}
+}
+
+/// Branch and bound over sets of exactly `picks` non-nested directories, largest first
+struct SetSearch<'a> {
+	fs: &'a FileSystem,
+	candidates: &'a [NodeId],
+	needed: u32,
+	chosen: Vec<NodeId>,
+	best: Option<(u32, Vec<NodeId>)>,
+}
+
+impl SetSearch<'_> {
+	fn search(&mut self, start: usize, picks: usize, freed: u32) {
+		if picks == 0 {
+			if freed >= self.needed && self.best.as_ref().is_none_or(|(best, _)| freed < *best) {
+				self.best = Some((freed, self.chosen.clone()));
+			}
+			return;
+		}
+		for i in start..self.candidates.len() {
+			let dir = self.candidates[i];
+			let size = self.fs.size(dir);
+			// Everything after this is no bigger, so nothing further on can reach the target
+			if (freed as u64) + (picks as u64) * (size as u64) < self.needed as u64 {
+				break;
+			}
+			if self
+				.best
+				.as_ref()
+				.is_some_and(|(best, _)| freed + size >= *best)
+			{
+				continue;
+			}
+			if self.chosen.iter().any(|c| self.fs.is_nested(*c, dir)) {
+				continue;
+			}
+			self.chosen.push(dir);
+			self.search(i + 1, picks - 1, freed + size);
+			self.chosen.pop();
+		}
+	}
+}
+
+/// Blank lines are harmless, anything else that couldn't be parsed is skipped with a warning
+fn unparsed(text: Cow<str>, line: usize, warnings: &mut Vec<Warning>) {
+	if !text.trim().is_empty() {
+		warnings.push(Warning::UnparsedLine {
+			line,
+			text: text.to_string(),
+		});
+	}
+}
+
+/// Something in the transcript that doesn't line up, the replay carries on regardless
+#[derive(Debug, PartialEq, Clone)]
+pub enum Warning {
+	/// A file was listed again with a different size, the latest size is kept
+	SizeChanged {
+		path: String,
+		was: u32,
+		now: u32,
+	},
+	/// Listed as a file in one place and a directory in another, the first is kept
+	KindChanged {
+		path: String,
+	},
+	/// Listed in the directory before but not this time
+	Missing {
+		path: String,
+	},
+	/// `cd` into something that was listed as a file
+	NotADirectory {
+		path: String,
+	},
+	UnknownCommand {
+		command: String,
+	},
+	/// A line that's neither a command nor output that could be understood, counted from 1
+	UnparsedLine {
+		line: usize,
+		text: String,
+	},
+	/// Input left over after parsing, starting on `line`
+	TrailingInput {
+		line: usize,
+		text: String,
+	},
+}
+
+impl fmt::Display for Warning {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Warning::SizeChanged { path, was, now } => {
+				write!(f, "{path} was {was} but is now listed as {now}")
+			}
+			Warning::KindChanged { path } => {
+				write!(f, "{path} is listed as both a file and a directory")
+			}
+			Warning::Missing { path } => write!(f, "{path} is missing from a later listing"),
+			Warning::NotADirectory { path } => write!(f, "can't cd into file {path}"),
+			Warning::UnknownCommand { command } => write!(f, "unknown command `{command}`"),
+			Warning::UnparsedLine { line, text } => {
+				write!(f, "line {line} couldn't be parsed: {text}")
+			}
+			Warning::TrailingInput { line, text } => {
+				write!(f, "input from line {line} was left unparsed: {text}")
+			}
+		}
+	}
+}
+
+/// Options for rendering the file system like `tree`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TreeOptions {
+	/// Show sizes like `7.7M` rather than in bytes
+	pub human_readable: bool,
+	/// Only descend this many levels below the root
+	pub max_depth: Option<usize>,
+}
+
+impl FileSystem {
+	/// Renders the tree like `tree`, with each node's size after its name
+	pub fn tree(&self, options: &TreeOptions) -> String {
+		let mut out = format!(
+			"/ ({})\n",
+			format_size(self.size(FileSystem::ROOT), options)
+		);
+		self.render_children(FileSystem::ROOT, "", 1, options, &mut out);
+		out
+	}
+
+	fn render_children(
+		&self,
+		dir: NodeId,
+		prefix: &str,
+		depth: usize,
+		options: &TreeOptions,
+		out: &mut String,
+	) {
+		if options.max_depth.is_some_and(|max| depth > max) {
+			return;
+		}
+		let children = self.children(dir);
+		for (i, child) in children.iter().enumerate() {
+			let last = i + 1 == children.len();
+			let branch = if last { "└── " } else { "├── " };
+			let size = format_size(self.size(*child), options);
+			out.push_str(&format!("{prefix}{branch}{} ({size})\n", self.name(*child)));
+
+			let indent = if last { "    " } else { "│   " };
+			self.render_children(
+				*child,
+				&format!("{prefix}{indent}"),
+				depth + 1,
+				options,
+				out,
+			);
+		}
+	}
+
+	/// Every directory with its total size, largest first like `du | sort -rn`
+	pub fn du(&self) -> Vec<(u32, String)> {
+		let mut usage: Vec<(u32, String)> = self
+			.dirs()
+			.map(|dir| (self.size(dir), self.path(dir)))
+			.collect();
+		usage.sort_by(|(a, _), (b, _)| b.cmp(a));
+		usage
+	}
+
+	/// Renders [FileSystem::du] one directory per line
+	pub fn du_report(&self, human_readable: bool) -> String {
+		let options = TreeOptions {
+			human_readable,
+			max_depth: None,
+		};
+		self.du()
+			.into_iter()
+			.map(|(size, path)| format!("{}\t{path}\n", format_size(size, &options)))
+			.collect()
+	}
+
+	/// Nested JSON of every directory and file, so transcripts can be diffed
+	pub fn to_json(&self) -> serde_json::Result<String> {
+		serde_json::to_string_pretty(&self.json_value(FileSystem::ROOT))
+	}
+
+	fn json_value(&self, id: NodeId) -> serde_json::Value {
+		let node = self.node(id);
+		match &node.kind {
+			NodeKind::Dir { children } => json!({
+				"name": node.name,
+				"type": "dir",
+				"size": node.size,
+				"children": children.iter().map(|c| self.json_value(*c)).collect::<Vec<_>>(),
+			}),
+			NodeKind::File => json!({
+				"name": node.name,
+				"type": "file",
+				"size": node.size,
+			}),
+		}
+	}
 }
 
-fn build_path(parts: &Vec<String>) -> String {
-	match parts.len() {
-		0 => "/".to_string(),
-		_ => format!("/{}/", parts.join("/")),
+/// Sizes in bytes, or with `K`/`M`/`G` suffixes like `du -h`
+fn format_size(size: u32, options: &TreeOptions) -> String {
+	if !options.human_readable {
+		return size.to_string();
+	}
+	let mut value = size as f64;
+	let mut units = ["K", "M", "G"].iter();
+	let mut unit = "";
+	while value >= 1024.0 {
+		match units.next() {
+			Some(next) => {
+				value /= 1024.0;
+				unit = next;
+			}
+			None => break,
+		}
+	}
+	match (unit, value < 10.0) {
+		("", _) => size.to_string(),
+		(_, true) => format!("{value:.1}{unit}"),
+		(_, false) => format!("{value:.0}{unit}"),
 	}
 }
 
 #[derive(Debug, PartialEq)]
-enum Command<'a> {
-	ChangeDirectory { target: Cow<'a, str> },
-	List { entries: Vec<ListEntry<'a>> },
+pub enum Command<'a> {
+	ChangeDirectory {
+		target: Cow<'a, str>,
+	},
+	List {
+		entries: Vec<ListEntry<'a>>,
+	},
+	/// Any other command, its `output` lines are skipped
+	Unknown {
+		command: Cow<'a, str>,
+		output: usize,
+	},
+	/// A line outside of any command's output that isn't a command
+	Unparsed {
+		text: Cow<'a, str>,
+	},
 }
 
 #[derive(Debug, PartialEq)]
-enum ListEntry<'b> {
-	File { size: u32, name: Cow<'b, str> },
-	Dir { name: Cow<'b, str> },
+pub enum ListEntry<'b> {
+	File {
+		size: u32,
+		name: Cow<'b, str>,
+	},
+	Dir {
+		name: Cow<'b, str>,
+	},
+	/// An output line that isn't a file or directory
+	Unparsed {
+		text: Cow<'b, str>,
+	},
 }
 
-fn parse_terminal(input: &str) -> IResult<&str, Vec<Command>> {
-	separated_list1(line_ending, command)(input)
+pub fn parse_terminal(input: &str) -> IResult<&str, Vec<Command<'_>>> {
+	separated_list1(line_ending, alt((command, unparsed_command)))(input)
 }
 
-fn command(input: &str) -> IResult<&str, Command> {
-	preceded(tag("$ "), alt((cd_command, ls_command)))(input)
+fn unparsed_command(input: &str) -> IResult<&str, Command<'_>> {
+	let (input, text) = not_line_ending(input)?;
+
+	Ok((
+		input,
+		Command::Unparsed {
+			text: Cow::Borrowed(text),
+		},
+	))
 }
 
-fn cd_command(input: &str) -> IResult<&str, Command> {
-	// let (input, name) = tag("cd")(input)?;
-	let (input, target) = preceded(
-		tuple((tag("cd"), multispace1)),
-		alt((tag("/"), tag(".."), alphanumeric1)),
-	)(input)?;
+fn command(input: &str) -> IResult<&str, Command<'_>> {
+	preceded(tag("$ "), alt((cd_command, ls_command, unknown_command)))(input)
+}
+
+fn cd_command(input: &str) -> IResult<&str, Command<'_>> {
+	let (input, target) = preceded(tuple((tag("cd"), space1)), not_line_ending)(input)?;
 
 	Ok((
 		input,
 		Command::ChangeDirectory {
-			target: Cow::Borrowed(target),
+			target: Cow::Borrowed(target.trim_end()),
 		},
 	))
 }
 
-fn ls_command(input: &str) -> IResult<&str, Command> {
-	let (input, _) = tag("ls")(input)?;
-	let (input, _) = newline(input)?;
-	let (input, entries) = separated_list1(line_ending, alt((dir_entry, file_entry)))(input)?;
+fn ls_command(input: &str) -> IResult<&str, Command<'_>> {
+	let (input, _) = terminated(tag("ls"), tuple((space0, peek(alt((line_ending, eof))))))(input)?;
+	let (input, entries) = many0(preceded(
+		line_ending,
+		alt((dir_entry, file_entry, unparsed_entry)),
+	))(input)?;
 
 	Ok((input, Command::List { entries }))
 }
 
-fn dir_entry(input: &str) -> IResult<&str, ListEntry> {
+fn unknown_command(input: &str) -> IResult<&str, Command<'_>> {
+	let (input, command) = not_line_ending(input)?;
+	let (input, output) = many0(preceded(line_ending, output_line))(input)?;
+
+	Ok((
+		input,
+		Command::Unknown {
+			command: Cow::Borrowed(command),
+			output: output.len(),
+		},
+	))
+}
+
+fn unparsed_entry(input: &str) -> IResult<&str, ListEntry<'_>> {
+	let (input, text) = output_line(input)?;
+
+	Ok((
+		input,
+		ListEntry::Unparsed {
+			text: Cow::Borrowed(text),
+		},
+	))
+}
+
+/// Any line that isn't a command
+fn output_line(input: &str) -> IResult<&str, &str> {
+	verify(not_line_ending, |line: &str| !line.starts_with('$'))(input)
+}
+
+fn dir_entry(input: &str) -> IResult<&str, ListEntry<'_>> {
 	let (input, _) = tag("dir ")(input)?;
-	let (input, name) = alphanumeric1(input)?;
+	let (input, name) = entry_name(input)?;
 
 	Ok((
 		input,
@@ -142,9 +834,9 @@ fn dir_entry(input: &str) -> IResult<&str, ListEntry> {
 	))
 }
 
-fn file_entry(input: &str) -> IResult<&str, ListEntry> {
+fn file_entry(input: &str) -> IResult<&str, ListEntry<'_>> {
 	let (input, size) = complete::u32(input)?;
-	let (input, name) = preceded(multispace1, not_line_ending)(input)?;
+	let (input, name) = preceded(space1, entry_name)(input)?;
 
 	Ok((
 		input,
@@ -155,10 +847,22 @@ fn file_entry(input: &str) -> IResult<&str, ListEntry> {
 	))
 }
 
+/// Names run to the end of the line, so they can hold anything other than `/`
+fn entry_name(input: &str) -> IResult<&str, &str> {
+	verify(not_line_ending, |name: &str| {
+		!name.trim().is_empty() && !name.contains('/')
+	})
+	.map(str::trim_end)
+	.parse(input)
+}
+
 #[cfg(test)]
 mod test {
 	use crate::day7::Command::ChangeDirectory;
-	use crate::day7::{command, parse_terminal, solve_part1, solve_part2, Command, ListEntry};
+	use crate::day7::{
+		command, load, parse_terminal, solve_part1, solve_part2, CleanupError, CleanupPlanner,
+		Command, FileSystem, ListEntry, TreeOptions, Warning,
+	};
 	use std::borrow::Cow;
 
 	const EXAMPLE: &str = r"$ cd /
@@ -248,6 +952,300 @@ dir e
 
 	#[test]
 	fn part2() {
-		assert_eq!(24933642, solve_part2(EXAMPLE))
+		assert_eq!(Ok(24933642), solve_part2(EXAMPLE));
+		// Only deleting everything frees enough
+		assert_eq!(
+			Ok(44000000),
+			solve_part2("$ cd /\n$ ls\ndir a\n41000000 big\n$ cd a\n$ ls\n3000000 small")
+		);
+	}
+
+	#[test]
+	fn file_system_tree() {
+		let (_, commands) = parse_terminal(EXAMPLE).unwrap();
+		let fs = FileSystem::from_commands(commands);
+
+		let sizes = [
+			("/", 48381165),
+			("/a", 94853),
+			("/a/e", 584),
+			("/d", 24933642),
+		];
+		for (path, size) in sizes {
+			assert_eq!(size, fs.size(fs.lookup(path).unwrap()), "{path}");
+		}
+
+		let log = fs.lookup("/d/d.log").unwrap();
+		assert_eq!("d.log", fs.name(log));
+		assert_eq!(8033020, fs.size(log));
+		assert_eq!("/d/d.log", fs.path(log));
+		assert_eq!(fs.lookup("/d"), fs.parent(log));
+		assert_eq!(None, fs.lookup("/d/x"));
+
+		let a = fs.lookup("/a/").unwrap();
+		let names = fs
+			.children(a)
+			.iter()
+			.map(|c| fs.name(*c))
+			.collect::<Vec<_>>();
+		assert_eq!(vec!["e", "f", "g", "h.lst"], names);
+
+		let walked = fs
+			.walk()
+			.map(|(id, depth)| format!("{}{}", " ".repeat(depth), fs.name(id)))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			vec![
+				"/", " a", "  e", "   i", "  f", "  g", "  h.lst", " b.txt", " c.dat", " d", "  j",
+				"  d.log", "  d.ext", "  k"
+			],
+			walked
+		);
+		assert_eq!(
+			vec!["/", "/a/", "/a/e/", "/d/"],
+			fs.dirs().map(|d| fs.path(d)).collect::<Vec<_>>()
+		);
+	}
+
+	#[test]
+	fn tree_report() {
+		let (_, commands) = parse_terminal(EXAMPLE).unwrap();
+		let fs = FileSystem::from_commands(commands);
+
+		let expected = "/ (48381165)
+├── a (94853)
+│   ├── e (584)
+│   │   └── i (584)
+│   ├── f (29116)
+│   ├── g (2557)
+│   └── h.lst (62596)
+├── b.txt (14848514)
+├── c.dat (8504156)
+└── d (24933642)
+    ├── j (4060174)
+    ├── d.log (8033020)
+    ├── d.ext (5626152)
+    └── k (7214296)
+";
+		assert_eq!(expected, fs.tree(&TreeOptions::default()));
+
+		let shallow = TreeOptions {
+			human_readable: true,
+			max_depth: Some(1),
+		};
+		let expected = "/ (46M)
+├── a (93K)
+├── b.txt (14M)
+├── c.dat (8.1M)
+└── d (24M)
+";
+		assert_eq!(expected, fs.tree(&shallow));
+	}
+
+	#[test]
+	fn du_report() {
+		let (_, commands) = parse_terminal(EXAMPLE).unwrap();
+		let fs = FileSystem::from_commands(commands);
+
+		assert_eq!(
+			"48381165\t/\n24933642\t/d/\n94853\t/a/\n584\t/a/e/\n",
+			fs.du_report(false)
+		);
+		assert_eq!(
+			"46M\t/\n24M\t/d/\n93K\t/a/\n584\t/a/e/\n",
+			fs.du_report(true)
+		);
+	}
+
+	#[test]
+	fn json_export() {
+		let (_, commands) = parse_terminal(EXAMPLE).unwrap();
+		let fs = FileSystem::from_commands(commands);
+
+		let json: serde_json::Value = serde_json::from_str(&fs.to_json().unwrap()).unwrap();
+		assert_eq!(48381165, json["size"]);
+		assert_eq!("a", json["children"][0]["name"]);
+		assert_eq!("dir", json["children"][0]["type"]);
+		assert_eq!(
+			"i",
+			json["children"][0]["children"][0]["children"][0]["name"]
+		);
+		assert_eq!("file", json["children"][3]["children"][1]["type"]);
+		assert_eq!(8033020, json["children"][3]["children"][1]["size"]);
+	}
+
+	#[test]
+	fn tolerant_parsing() {
+		let transcript = r"$ cd /
+$ ls
+dir my-dir
+12 x.d
+$ pwd
+/
+$ cd my-dir/sub dir
+$ ls
+$ cd ../../my-dir
+$ ls
+5 notes 2022.txt
+$ cd /
+$ ls
+dir my-dir
+20 x.d
+$ cd x.d";
+		let (tail, commands) = parse_terminal(transcript).unwrap();
+		assert!(tail.is_empty());
+		assert_eq!(
+			Command::Unknown {
+				command: Cow::Borrowed("pwd"),
+				output: 1
+			},
+			commands[2]
+		);
+		assert_eq!(
+			ChangeDirectory {
+				target: Cow::Borrowed("my-dir/sub dir")
+			},
+			commands[3]
+		);
+		assert_eq!(Command::List { entries: vec![] }, commands[4]);
+
+		let (fs, warnings) = FileSystem::replay(commands);
+		assert_eq!(
+			vec![
+				Warning::UnknownCommand {
+					command: "pwd".to_string()
+				},
+				Warning::SizeChanged {
+					path: "/x.d".to_string(),
+					was: 12,
+					now: 20
+				},
+				Warning::NotADirectory {
+					path: "/x.d".to_string()
+				},
+			],
+			warnings
+		);
+		assert_eq!(25, fs.size(FileSystem::ROOT));
+		assert!(fs.lookup("/my-dir/sub dir").is_some());
+		assert_eq!(5, fs.size(fs.lookup("/my-dir/notes 2022.txt").unwrap()));
+	}
+
+	#[test]
+	fn unparsed_lines() {
+		let transcript = "$ cd /\n$ ls\n12 a\n???\n100 b\ndir d\n\n$ cd d\nhello\n$ ls\n";
+		let (fs, warnings) = load(transcript);
+		assert_eq!(
+			vec![
+				Warning::UnparsedLine {
+					line: 4,
+					text: "???".to_string()
+				},
+				Warning::UnparsedLine {
+					line: 9,
+					text: "hello".to_string()
+				},
+			],
+			warnings
+		);
+		assert_eq!(112, fs.size(FileSystem::ROOT));
+		assert!(fs.lookup("/d").is_some());
+
+		// A trailing newline is fine, so is a missing one
+		assert_eq!(
+			Vec::<Warning>::new(),
+			load(&EXAMPLE.replace("7214296 k", "7214296 k\n")).1
+		);
+		assert_eq!(95437, {
+			let (fs, _) = load(EXAMPLE);
+			fs.dirs()
+				.map(|dir| fs.size(dir))
+				.filter(|size| *size <= 100000)
+				.sum::<u32>()
+		});
+	}
+
+	#[test]
+	fn inconsistent_listings() {
+		let transcript = r"$ cd /
+$ ls
+dir a
+1 b
+$ ls
+2 a
+$ cd a
+$ ls";
+		let (_, commands) = parse_terminal(transcript).unwrap();
+		let (fs, warnings) = FileSystem::replay(commands);
+		assert_eq!(
+			vec![
+				Warning::Missing {
+					path: "/b".to_string()
+				},
+				Warning::KindChanged {
+					path: "/a/".to_string()
+				},
+			],
+			warnings
+		);
+		assert_eq!(1, fs.size(FileSystem::ROOT));
+	}
+
+	#[test]
+	fn cleanup_planner() {
+		let (_, commands) = parse_terminal(EXAMPLE).unwrap();
+		let fs = FileSystem::from_commands(commands);
+
+		let plan = CleanupPlanner::default().plan(&fs).unwrap();
+		assert_eq!(8381165, plan.needed);
+		assert_eq!(fs.lookup("/d"), plan.smallest_dir);
+		assert_eq!(vec![fs.lookup("/d").unwrap()], plan.minimal_set);
+
+		// `d` is one short, adding `e` rather than all of `a` deletes the least
+		let planner = CleanupPlanner {
+			disk_size: 48381165,
+			required_free_space: 24933642 + 1,
+		};
+		let plan = planner.plan(&fs).unwrap();
+		assert_eq!(Some(FileSystem::ROOT), plan.smallest_dir);
+		assert_eq!(
+			vec![fs.lookup("/d").unwrap(), fs.lookup("/a/e").unwrap()],
+			plan.minimal_set
+		);
+		assert_eq!(24933642 + 584, plan.freed);
+
+		let roomy = CleanupPlanner {
+			disk_size: 100000000,
+			required_free_space: 30000000,
+		};
+		let plan = roomy.plan(&fs).unwrap();
+		assert_eq!((0, None), (plan.needed, plan.smallest_dir));
+		assert!(plan.minimal_set.is_empty());
+
+		// The tree is bigger than the disk
+		let small = CleanupPlanner {
+			disk_size: 30000000,
+			required_free_space: 0,
+		};
+		assert_eq!(18381165, small.plan(&fs).unwrap().needed);
+
+		let tiny = CleanupPlanner {
+			disk_size: 10000000,
+			required_free_space: 0,
+		};
+		let plan = tiny.plan(&fs).unwrap();
+		assert_eq!(38381165, plan.needed);
+		assert_eq!(Some(FileSystem::ROOT), plan.smallest_dir);
+		assert_eq!(vec![FileSystem::ROOT], plan.minimal_set);
+		assert_eq!(48381165, plan.freed);
+
+		let impossible = CleanupPlanner {
+			disk_size: 10000000,
+			required_free_space: 20000000,
+		};
+		assert!(matches!(
+			impossible.plan(&fs),
+			Err(CleanupError::DiskTooSmall { .. })
+		));
 	}
 }
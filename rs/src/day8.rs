@@ -1,9 +1,14 @@
+use crate::netpbm::{comment, join};
+use std::fmt::Display;
 use yaah::*;
 
 type TreeRow = Vec<char>;
 type TreeRows = Vec<TreeRow>;
 type VisibilityPlot = Vec<Vec<bool>>;
 
+/// How far a tree can see looking up, down, left and right
+pub type ViewingDistances = [u32; 4];
+
 #[aoc_generator(day8)]
 fn gen(input: &'static str) -> Vec<Vec<char>> {
 	input.lines().map(|s| s.chars().collect()).collect()
@@ -11,10 +16,7 @@ fn gen(input: &'static str) -> Vec<Vec<char>> {
 
 #[aoc(day8, part1)]
 fn solve_part1(rows: &TreeRows) -> u32 {
-	let columns: TreeRows = transpose(rows);
-	let visibility_plot = visibility_plot(rows, &columns);
-
-	visibility_plot
+	visibility(rows)
 		.iter()
 		.map(|row| row.iter().filter(|v| **v).count() as u32)
 		.sum()
@@ -22,110 +24,362 @@ fn solve_part1(rows: &TreeRows) -> u32 {
 
 #[aoc(day8, part2)]
 fn solve_part2(trees: &TreeRows) -> u32 {
-	trees
+	scenic_scores(trees).into_iter().flatten().max().unwrap()
+}
+
+/// Which trees can be seen from outside the forest, checking each row and column once
+pub fn visibility(rows: &TreeRows) -> VisibilityPlot {
+	let columns = transpose(rows);
+	let from_rows = sweep_both_ways(rows, line_visibility);
+	let from_columns = transpose(&sweep_both_ways(&columns, line_visibility));
+
+	from_rows
 		.iter()
-		.enumerate()
-		.flat_map(|(y, row)| {
+		.zip(from_columns.iter())
+		.map(|(row, column)| {
 			row.iter()
-				.enumerate()
-				.map(|(x, treehouse)| scenic_score(x, y, trees, *treehouse))
-				.collect::<Vec<u32>>()
+				.zip(column.iter())
+				.map(|((left, right), (up, down))| *left || *right || *up || *down)
+				.collect()
 		})
-		.max()
-		.unwrap()
+		.collect()
 }
 
-fn scenic_score(x: usize, y: usize, trees: &TreeRows, treehouse: char) -> u32 {
-	[
-		up(x, y, trees),
-		down(x, y, trees),
-		left(x, y, trees),
-		right(x, y, trees),
-	]
-	.iter()
-	.map(|sightline| visible_trees(treehouse, sightline))
-	.product::<u32>()
+/// Viewing distances for every tree, in the order up, down, left, right
+pub fn viewing_distances(rows: &TreeRows) -> Vec<Vec<ViewingDistances>> {
+	let columns = transpose(rows);
+	let from_rows = sweep_both_ways(rows, line_distances);
+	let from_columns = transpose(&sweep_both_ways(&columns, line_distances));
+
+	from_rows
+		.iter()
+		.zip(from_columns.iter())
+		.map(|(row, column)| {
+			row.iter()
+				.zip(column.iter())
+				.map(|((left, right), (up, down))| [*up, *down, *left, *right])
+				.collect()
+		})
+		.collect()
 }
 
-fn up(x: usize, y: usize, trees: &TreeRows) -> TreeRow {
-	(0..y).rev().map(|i| trees[i][x]).collect()
+pub fn scenic_scores(rows: &TreeRows) -> Vec<Vec<u32>> {
+	scores_from(&viewing_distances(rows))
 }
 
-fn down(x: usize, y: usize, trees: &TreeRows) -> TreeRow {
-	let size = trees.len();
-	((y + 1)..size).map(|i| trees[i][x]).collect()
+fn scores_from(distances: &[Vec<ViewingDistances>]) -> Vec<Vec<u32>> {
+	distances
+		.iter()
+		.map(|row| row.iter().map(|d| d.iter().product()).collect())
+		.collect()
 }
 
-fn left(x: usize, y: usize, trees: &TreeRows) -> TreeRow {
-	(0..x).rev().map(|i| trees[y][i]).collect()
+/// Best spot for the treehouse, the first found in reading order if several tie
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Treehouse {
+	pub x: usize,
+	pub y: usize,
+	pub score: u32,
+	pub distances: ViewingDistances,
 }
 
-fn right(x: usize, y: usize, trees: &TreeRows) -> TreeRow {
-	let size = trees[0].len();
-	((x + 1)..size).map(|i| trees[y][i]).collect()
+pub fn best_treehouse(rows: &TreeRows) -> Option<Treehouse> {
+	best_from(&viewing_distances(rows))
 }
 
-fn visible_trees(treehouse: char, trees: &TreeRow) -> u32 {
-	match trees
+fn best_from(distances: &[Vec<ViewingDistances>]) -> Option<Treehouse> {
+	distances
 		.iter()
 		.enumerate()
-		.find_map(|(idx, tree)| match *tree >= treehouse {
-			true => Some(&trees[..=idx]),
-			false => None,
-		}) {
-		Some(tr) => tr.len() as u32,
-		None => trees.len() as u32,
+		.flat_map(|(y, row)| {
+			row.iter().enumerate().map(move |(x, distances)| Treehouse {
+				x,
+				y,
+				score: distances.iter().product(),
+				distances: *distances,
+			})
+		})
+		.fold(None, |best: Option<Treehouse>, tree| match best {
+			Some(b) if b.score >= tree.score => Some(b),
+			_ => Some(tree),
+		})
+}
+
+/// Visibility and scenic scores for the whole forest, ready to export as images or CSV
+#[derive(Debug, Clone)]
+pub struct HeatMap {
+	pub visibility: VisibilityPlot,
+	pub scores: Vec<Vec<u32>>,
+	pub best: Option<Treehouse>,
+}
+
+impl HeatMap {
+	pub fn new(rows: &TreeRows) -> Self {
+		let distances = viewing_distances(rows);
+		HeatMap {
+			visibility: visibility(rows),
+			scores: scores_from(&distances),
+			best: best_from(&distances),
+		}
+	}
+
+	fn width(&self) -> usize {
+		self.scores.first().map_or(0, |row| row.len())
+	}
+
+	fn height(&self) -> usize {
+		self.scores.len()
+	}
+
+	/// Scenic scores scaled to `0..=255`
+	fn scaled_scores(&self) -> Vec<Vec<u32>> {
+		let max = self
+			.scores
+			.iter()
+			.flatten()
+			.copied()
+			.max()
+			.unwrap_or(0)
+			.max(1) as u64;
+		self.scores
+			.iter()
+			.map(|row| row.iter().map(|s| (*s as u64 * 255 / max) as u32).collect())
+			.collect()
+	}
+
+	/// Image header with the best treehouse noted in a comment
+	fn header(&self, magic: &str, max: u32) -> String {
+		let mut header = format!("{magic}\n");
+		if let Some(Treehouse {
+			x,
+			y,
+			score,
+			distances: [up, down, left, right],
+		}) = self.best
+		{
+			header.push_str(&comment(&format!(
+				"best treehouse x={x} y={y} score={score} up={up} down={down} left={left} right={right}"
+			)));
+		}
+		header.push_str(&format!("{} {}\n{max}\n", self.width(), self.height()));
+		header
+	}
+
+	/// Plain PGM with visible trees white and hidden trees black
+	pub fn visibility_pgm(&self) -> String {
+		let pixels = self
+			.visibility
+			.iter()
+			.map(|row| join(row.iter().map(|v| *v as u8)))
+			.collect::<Vec<String>>()
+			.join("\n");
+		format!("{}{pixels}\n", self.header("P2", 1))
+	}
+
+	/// Plain PGM with the brightest trees having the best scenic scores
+	pub fn scenic_pgm(&self) -> String {
+		let pixels = self
+			.scaled_scores()
+			.iter()
+			.map(|row| join(row.iter()))
+			.collect::<Vec<String>>()
+			.join("\n");
+		format!("{}{pixels}\n", self.header("P2", 255))
+	}
+
+	/// Plain PPM with scenic scores in red, visible trees in green and the best treehouse in blue
+	pub fn ppm(&self) -> String {
+		let scaled = self.scaled_scores();
+		let pixels =
+			scaled
+				.iter()
+				.zip(self.visibility.iter())
+				.enumerate()
+				.map(|(y, (scores, visible))| {
+					join(scores.iter().zip(visible.iter()).enumerate().map(
+						|(x, (score, visible))| {
+							let best = self.best.is_some_and(|b| (b.x, b.y) == (x, y));
+							format!("{score} {} {}", *visible as u8 * 255, best as u8 * 255)
+						},
+					))
+				})
+				.collect::<Vec<String>>()
+				.join("\n");
+		format!("{}{pixels}\n", self.header("P3", 255))
+	}
+
+	pub fn visibility_csv(&self) -> String {
+		to_csv(
+			self.visibility
+				.iter()
+				.map(|row| row.iter().map(|v| *v as u8)),
+		)
+	}
+
+	pub fn scenic_csv(&self) -> String {
+		to_csv(self.scores.iter().map(|row| row.iter()))
+	}
+
+	/// One row CSV of the best treehouse and its viewing distances
+	pub fn treehouse_csv(&self) -> String {
+		let mut csv = "x,y,score,up,down,left,right\n".to_string();
+		if let Some(Treehouse {
+			x,
+			y,
+			score,
+			distances: [up, down, left, right],
+		}) = self.best
+		{
+			csv.push_str(&format!("{x},{y},{score},{up},{down},{left},{right}\n"));
+		}
+		csv
 	}
 }
 
-fn transpose(rows: &TreeRows) -> TreeRows {
-	rows[0]
-		.iter()
-		.enumerate()
-		.map(|(i, _)| rows.iter().map(|row| row[i]).collect::<TreeRow>())
+fn to_csv<T: Display, R: Iterator<Item = T>>(rows: impl Iterator<Item = R>) -> String {
+	rows.map(|row| row.map(|v| v.to_string()).collect::<Vec<_>>().join(",") + "\n")
 		.collect()
 }
 
-fn transpose_plot(rows: &VisibilityPlot) -> VisibilityPlot {
-	rows[0]
+/// Applies `f` to each line forwards and backwards, pairing up the results for each tree
+fn sweep_both_ways<T>(lines: &TreeRows, f: fn(&[char]) -> Vec<T>) -> Vec<Vec<(T, T)>> {
+	lines
 		.iter()
-		.enumerate()
-		.map(|(i, _)| rows.iter().map(|row| row[i]).collect::<Vec<bool>>())
+		.map(|line| {
+			let reversed: TreeRow = line.iter().rev().copied().collect();
+			let mut backwards = f(&reversed);
+			backwards.reverse();
+			f(line).into_iter().zip(backwards).collect()
+		})
 		.collect()
 }
 
-fn taller(tree: char, others: &[char]) -> bool {
-	others.iter().all(|other| tree > *other)
+/// Whether each tree is taller than every tree before it in the line
+fn line_visibility(line: &[char]) -> Vec<bool> {
+	let mut tallest = None;
+	line.iter()
+		.map(|tree| {
+			let visible = tallest.is_none_or(|t| *tree > t);
+			tallest = tallest.max(Some(*tree));
+			visible
+		})
+		.collect()
 }
 
-fn directional_visibility(row: &TreeRow) -> Vec<bool> {
-	row.iter()
+/// How many trees each tree can see looking back along the line. The stack holds the trees
+/// that haven't been blocked yet, so each tree is pushed and popped at most once.
+fn line_distances(line: &[char]) -> Vec<u32> {
+	let mut stack: Vec<usize> = vec![];
+	line.iter()
 		.enumerate()
-		.map(|(i, tree)| (*tree, row.split_at(i)))
-		.map(|(tree, (left, tail))| (left, tree, &tail[1..]))
-		.map(|(left, tree, right)| taller(tree, left) || taller(tree, right))
+		.map(|(i, tree)| {
+			while stack.last().is_some_and(|j| line[*j] < *tree) {
+				stack.pop();
+			}
+			let distance = i - stack.last().copied().unwrap_or(0);
+			stack.push(i);
+			distance as u32
+		})
 		.collect()
 }
 
-fn visibility_plot(rows: &TreeRows, columns: &TreeRows) -> VisibilityPlot {
-	rows.iter()
-		.map(directional_visibility)
-		.zip(transpose_plot(&columns.iter().map(directional_visibility).collect()).iter())
-		.map(|(row_visibility, column_visibility)| {
-			row_visibility
-				.iter()
-				.zip(column_visibility.iter())
-				.map(|(row_value, column_value)| *row_value || *column_value)
-				.collect::<Vec<bool>>()
-		})
-		.collect::<VisibilityPlot>()
+fn transpose<T: Copy>(rows: &[Vec<T>]) -> Vec<Vec<T>> {
+	let width = rows.first().map_or(0, |row| row.len());
+	(0..width)
+		.map(|i| rows.iter().map(|row| row[i]).collect::<Vec<T>>())
+		.collect()
+}
+
+/// The original brute force implementations, kept to check the linear ones against
+#[cfg(test)]
+mod naive {
+	use super::{TreeRow, TreeRows, VisibilityPlot};
+
+	pub fn scenic_score(x: usize, y: usize, trees: &TreeRows, treehouse: char) -> u32 {
+		[
+			up(x, y, trees),
+			down(x, y, trees),
+			left(x, y, trees),
+			right(x, y, trees),
+		]
+		.iter()
+		.map(|sightline| visible_trees(treehouse, sightline))
+		.product::<u32>()
+	}
+
+	pub fn up(x: usize, y: usize, trees: &TreeRows) -> TreeRow {
+		(0..y).rev().map(|i| trees[i][x]).collect()
+	}
+
+	pub fn down(x: usize, y: usize, trees: &TreeRows) -> TreeRow {
+		let size = trees.len();
+		((y + 1)..size).map(|i| trees[i][x]).collect()
+	}
+
+	pub fn left(x: usize, y: usize, trees: &TreeRows) -> TreeRow {
+		(0..x).rev().map(|i| trees[y][i]).collect()
+	}
+
+	pub fn right(x: usize, y: usize, trees: &TreeRows) -> TreeRow {
+		let size = trees[0].len();
+		((x + 1)..size).map(|i| trees[y][i]).collect()
+	}
+
+	pub fn visible_trees(treehouse: char, trees: &TreeRow) -> u32 {
+		match trees
+			.iter()
+			.enumerate()
+			.find_map(|(idx, tree)| match *tree >= treehouse {
+				true => Some(&trees[..=idx]),
+				false => None,
+			}) {
+			Some(tr) => tr.len() as u32,
+			None => trees.len() as u32,
+		}
+	}
+
+	pub fn transpose_plot(rows: &VisibilityPlot) -> VisibilityPlot {
+		rows[0]
+			.iter()
+			.enumerate()
+			.map(|(i, _)| rows.iter().map(|row| row[i]).collect::<Vec<bool>>())
+			.collect()
+	}
+
+	pub fn taller(tree: char, others: &[char]) -> bool {
+		others.iter().all(|other| tree > *other)
+	}
+
+	pub fn directional_visibility(row: &TreeRow) -> Vec<bool> {
+		row.iter()
+			.enumerate()
+			.map(|(i, tree)| (*tree, row.split_at(i)))
+			.map(|(tree, (left, tail))| (left, tree, &tail[1..]))
+			.map(|(left, tree, right)| taller(tree, left) || taller(tree, right))
+			.collect()
+	}
+
+	pub fn visibility_plot(rows: &TreeRows, columns: &TreeRows) -> VisibilityPlot {
+		rows.iter()
+			.map(directional_visibility)
+			.zip(transpose_plot(&columns.iter().map(directional_visibility).collect()).iter())
+			.map(|(row_visibility, column_visibility)| {
+				row_visibility
+					.iter()
+					.zip(column_visibility.iter())
+					.map(|(row_value, column_value)| *row_value || *column_value)
+					.collect::<Vec<bool>>()
+			})
+			.collect::<VisibilityPlot>()
+	}
 }
 
 #[cfg(test)]
 mod test {
+	use crate::day8::naive::{scenic_score, visibility_plot, visible_trees};
 	use crate::day8::{
-		gen, scenic_score, solve_part1, solve_part2, transpose, visibility_plot, visible_trees,
-		TreeRows, VisibilityPlot,
+		best_treehouse, gen, scenic_scores, solve_part1, solve_part2, transpose, viewing_distances,
+		visibility, HeatMap, TreeRows, Treehouse, VisibilityPlot,
 	};
 
 	const EXAMPLE: &str = r"30373
@@ -228,4 +482,134 @@ mod test {
 			})
 			.for_each(|s| println!("{:?}", s))
 	}
+
+	/// Deterministic pseudo-random forest of the given size
+	fn forest(width: usize, height: usize, seed: u32) -> TreeRows {
+		let mut state = seed;
+		(0..height)
+			.map(|_| {
+				(0..width)
+					.map(|_| {
+						state = state.wrapping_mul(1103515245).wrapping_add(12345);
+						char::from(b'0' + ((state >> 16) % 10) as u8)
+					})
+					.collect()
+			})
+			.collect()
+	}
+
+	#[test]
+	fn matches_naive() {
+		let forests = [
+			gen(EXAMPLE),
+			forest(13, 7, 1),
+			forest(7, 13, 2),
+			forest(1, 9, 3),
+			forest(9, 1, 4),
+			forest(40, 40, 5),
+		];
+		for trees in forests {
+			let columns: TreeRows = transpose(&trees);
+			assert_eq!(visibility_plot(&trees, &columns), visibility(&trees));
+
+			let expected = trees
+				.iter()
+				.enumerate()
+				.map(|(y, row)| {
+					row.iter()
+						.enumerate()
+						.map(|(x, tree)| scenic_score(x, y, &trees, *tree))
+						.collect::<Vec<u32>>()
+				})
+				.collect::<Vec<_>>();
+			assert_eq!(expected, scenic_scores(&trees));
+		}
+	}
+
+	#[test]
+	fn example_viewing_distances() {
+		let distances = viewing_distances(&gen(EXAMPLE));
+		assert_eq!([1, 2, 1, 2], distances[1][2]);
+		assert_eq!([2, 1, 2, 2], distances[3][2]);
+		assert_eq!([0, 2, 0, 2], distances[0][0]);
+	}
+
+	#[test]
+	fn best_treehouse_location() {
+		assert_eq!(
+			Some(Treehouse {
+				x: 2,
+				y: 3,
+				score: 8,
+				distances: [2, 1, 2, 2]
+			}),
+			best_treehouse(&gen(EXAMPLE))
+		);
+	}
+
+	#[test]
+	fn heat_map_export() {
+		let heat_map = HeatMap::new(&gen(EXAMPLE));
+
+		let pgm = heat_map.visibility_pgm();
+		let mut lines = pgm.lines();
+		assert_eq!(Some("P2"), lines.next());
+		assert_eq!(
+			Some("# best treehouse x=2 y=3 score=8 up=2 down=1 left=2 right=2"),
+			lines.next()
+		);
+		assert_eq!(Some("5 5"), lines.next());
+		assert_eq!(Some("1"), lines.next());
+		assert_eq!(Some("1 1 1 1 1"), lines.next());
+		assert_eq!(Some("1 1 1 0 1"), lines.next());
+
+		let scenic = heat_map.scenic_pgm();
+		assert_eq!(Some("0 31 255 95 0"), scenic.lines().nth(7));
+
+		let ppm = heat_map.ppm();
+		assert!(ppm.starts_with("P3\n"));
+		assert_eq!(
+			Some("0 255 0 31 0 0 255 255 255 95 0 0 0 255 0"),
+			ppm.lines().nth(7)
+		);
+
+		assert_eq!(
+			"0,0,0,0,0\n0,1,4,1,0\n0,6,1,2,0\n0,1,8,3,0\n0,0,0,0,0\n",
+			heat_map.scenic_csv()
+		);
+		assert!(heat_map
+			.visibility_csv()
+			.starts_with("1,1,1,1,1\n1,1,1,0,1\n"));
+		assert_eq!(
+			"x,y,score,up,down,left,right\n2,3,8,2,1,2,2\n",
+			heat_map.treehouse_csv()
+		);
+
+		// Long rows wrap like plain Netpbm expects
+		let wide = HeatMap::new(&gen("3333333333333333333333333333333333333333"));
+		let pgm = wide.visibility_pgm();
+		let rows: Vec<&str> = pgm.lines().skip(4).collect();
+		assert_eq!(vec![["1"; 35].join(" ").as_str(), "1 1 1 1 1"], rows);
+		assert!(wide.ppm().lines().all(|line| line.len() <= 70));
+
+		// So does the comment about a treehouse in a large forest
+		let large = HeatMap {
+			best: Some(Treehouse {
+				x: 4096,
+				y: 8191,
+				score: 1_234_567_890,
+				distances: [4096, 1234, 8191, 2048],
+			}),
+			..wide
+		};
+		let pgm = large.scenic_pgm();
+		assert_eq!(
+			vec![
+				"# best treehouse x=4096 y=8191 score=1234567890 up=4096 down=1234",
+				"# left=8191 right=2048"
+			],
+			pgm.lines().skip(1).take(2).collect::<Vec<_>>()
+		);
+		assert!(pgm.lines().all(|line| line.len() <= 70));
+	}
 }
@@ -1,4 +1,7 @@
-use nom::character::complete::{char, line_ending, one_of, u8 as nom_u8};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{char, line_ending, u32 as nom_u32};
+use nom::combinator::value;
 use nom::multi::separated_list1;
 use nom::sequence::separated_pair;
 use nom::IResult;
@@ -12,164 +15,227 @@ fn gen(input: &'static str) -> Vec<Motion> {
 
 #[aoc(day9, part1)]
 fn solve_part1(motions: &Vec<Motion>) -> u32 {
-	let mut rope = Rope::knots(vec![(0, 0), (0, 0)]);
+	let mut rope = Rope::new(2);
 
 	for motion in motions {
 		rope.advance(motion);
 	}
 	#[cfg(feature = "debug")]
-	rope.print_history();
-	rope.history.len() as u32
+	println!(
+		"{}",
+		rope.render_visited(rope.positions().len() - 1).unwrap()
+	);
+	rope.tail_visited() as u32
 }
 
 #[aoc(day9, part2)]
 fn solve_part2(motions: &Vec<Motion>) -> u32 {
-	let mut rope = Rope::knots(vec![
-		(0, 0),
-		(0, 0),
-		(0, 0),
-		(0, 0),
-		(0, 0),
-		(0, 0),
-		(0, 0),
-		(0, 0),
-		(0, 0),
-		(0, 0),
-	]);
+	let mut rope = Rope::new(10);
 
 	for motion in motions {
 		rope.advance(motion);
 	}
 	#[cfg(feature = "debug")]
-	rope.print_history();
-	rope.history.len() as u32
+	println!(
+		"{}",
+		rope.render_visited(rope.positions().len() - 1).unwrap()
+	);
+	rope.tail_visited() as u32
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Motion {
-	Up(u8),
-	Down(u8),
-	Left(u8),
-	Right(u8),
+	Up(u32),
+	Down(u32),
+	Left(u32),
+	Right(u32),
+	UpLeft(u32),
+	UpRight(u32),
+	DownLeft(u32),
+	DownRight(u32),
 }
 
-type Position = (i32, i32);
+impl Motion {
+	pub fn steps(&self) -> u32 {
+		match self {
+			Motion::Up(steps)
+			| Motion::Down(steps)
+			| Motion::Left(steps)
+			| Motion::Right(steps)
+			| Motion::UpLeft(steps)
+			| Motion::UpRight(steps)
+			| Motion::DownLeft(steps)
+			| Motion::DownRight(steps) => *steps,
+		}
+	}
+}
+
+pub type Position = (i32, i32);
 
-#[derive(Debug, PartialEq)]
-struct Rope {
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rope {
 	knots: Vec<Position>,
-	history: HashSet<Position>,
+	/// Positions visited by each knot, indexed like `knots`
+	history: Vec<HashSet<Position>>,
 }
 
 impl Rope {
+	/// A rope of `n` knots all starting at the origin
+	///
+	/// # Panics
+	/// If `n` is zero, a rope needs at least one knot
+	pub fn new(n: usize) -> Self {
+		Self::knots(vec![(0, 0); n])
+	}
+
+	/// # Panics
+	/// If `knots` is empty
 	pub fn knots(knots: Vec<Position>) -> Self {
-		Self {
-			knots,
-			history: HashSet::from([(0, 0)]),
-		}
+		assert!(!knots.is_empty(), "a rope needs at least one knot");
+		let history = knots.iter().map(|knot| HashSet::from([*knot])).collect();
+		Self { knots, history }
 	}
 
-	fn advance(&mut self, motion: &Motion) {
-		let step = translation_step(motion);
+	pub fn positions(&self) -> &[Position] {
+		&self.knots
+	}
+
+	pub fn head(&self) -> Position {
+		self.knots[0]
+	}
+
+	pub fn tail(&self) -> Position {
+		*self.knots.last().unwrap()
+	}
+
+	/// Cells visited by knot `k`, counting its starting position
+	pub fn visited(&self, k: usize) -> Option<&HashSet<Position>> {
+		self.history.get(k)
+	}
 
-		let steps = match motion {
-			Motion::Up(steps) => *steps as usize,
-			Motion::Down(steps) => *steps as usize,
-			Motion::Left(steps) => *steps as usize,
-			Motion::Right(steps) => *steps as usize,
-		};
+	/// Number of cells visited by every knot, head first
+	pub fn visited_counts(&self) -> Vec<usize> {
+		self.history.iter().map(|visited| visited.len()).collect()
+	}
 
-		(0..steps).for_each(|_| self.execute_step(step));
+	pub fn tail_visited(&self) -> usize {
+		self.history.last().unwrap().len()
+	}
+
+	pub fn advance(&mut self, motion: &Motion) {
+		let step = translation_step(motion);
+		(0..motion.steps()).for_each(|_| self.execute_step(step));
 	}
 
 	fn execute_step(&mut self, step: Position) {
-		for i in 0..(self.knots.len()) {
-			let knot = self.knots[i];
-			if i == 0 {
-				self.knots[i] = translate(knot, step);
-			} else {
-				let previous = self.knots[i - 1];
-				if let Some(new_position) = tail_move(previous, knot) {
+		self.knots[0] = translate(self.knots[0], step);
+		self.history[0].insert(self.knots[0]);
+		for i in 1..self.knots.len() {
+			match tail_move(self.knots[i - 1], self.knots[i]) {
+				Some(new_position) => {
 					self.knots[i] = new_position;
-					if i == self.knots.len() - 1 {
-						self.history.insert(new_position);
-					}
+					self.history[i].insert(new_position);
 				}
+				// Knots further down can't move if this one didn't
+				None => break,
 			}
 		}
 		#[cfg(feature = "debug")]
-		self.print();
+		println!("{}\n", self.render());
 	}
 
-	/// Attempted to print the state of the Knots in the format given
-	#[cfg(feature = "debug")]
-	fn print(&self) {
-		let head = self.knots[0];
-		let tail = self.knots.last().unwrap();
-
-		println!("=== H{:?} T{:?} ===", head, tail);
-		let lines: Vec<String> = (-5..18)
-			.into_iter()
-			.map(|y| {
-				(-11..15)
-					.into_iter()
-					.map(|x| match (x, y) {
-						(x, y) if self.knots.contains(&(x, y)) => {
-							match self.knots.iter().enumerate().find_map(|(i, knot)| {
-								match (x, y) == *knot {
-									true => Some(i),
-									false => None,
-								}
-							}) {
-								Some(i) => match i {
-									0 => 'H',
-									n if n == self.knots.len() - 1 => 'T',
-									_ => (i).to_string().chars().next().unwrap(),
-								},
-								None => ' ',
-							}
-						}
-						(0, 0) => 's',
-						_ => '.',
-					})
-					.collect::<String>()
-			})
-			.rev()
-			.collect();
-
-		for line in lines {
-			println!("{line}");
+	/// Draws the knots in the puzzle's style, sized to fit them and the start
+	pub fn render(&self) -> String {
+		render_knots(&self.knots)
+	}
+
+	/// Draws the cells visited by knot `k`, marking the start with `s`
+	pub fn render_visited(&self, k: usize) -> Option<String> {
+		let visited = self.history.get(k)?;
+		Some(render(visited.iter().copied(), |position| match position {
+			(0, 0) => 's',
+			position if visited.contains(&position) => '#',
+			_ => '.',
+		}))
+	}
+
+	/// Steps through `motions` one cell at a time, yielding every knot's position after each step
+	pub fn replay(self, motions: &[Motion]) -> Replay<'_> {
+		Replay {
+			rope: self,
+			motions: motions.iter(),
+			current: None,
 		}
-		println!("");
 	}
+}
 
-	#[cfg(feature = "debug")]
-	fn print_history(&self) {
-		let xs: Vec<i32> = self.history.iter().map(|p| p.0).collect();
-		let xmin = *xs.iter().min().unwrap() - 1;
-		let xmax = *xs.iter().max().unwrap() + 2;
-
-		let ys: Vec<i32> = self.history.iter().map(|p| p.1).collect();
-		let ymin = *ys.iter().min().unwrap() - 1;
-		let ymax = *ys.iter().max().unwrap() + 2;
-
-		(ymin..ymax)
-			.into_iter()
-			.map(|y| {
-				(xmin..xmax)
-					.into_iter()
-					.map(|x| match (x, y) {
-						(0, 0) => 's',
-						(x, y) if self.history.contains(&(x, y)) => '#',
-						_ => '.',
-					})
-					.collect::<String>()
-			})
-			.rev()
-			.for_each(|line| println!("{line}"))
+/// Iterator over the knot positions after each single step of a series of motions
+pub struct Replay<'a> {
+	rope: Rope,
+	motions: std::slice::Iter<'a, Motion>,
+	/// Direction and remaining steps of the motion in progress
+	current: Option<(Position, u32)>,
+}
+
+impl Replay<'_> {
+	pub fn rope(&self) -> &Rope {
+		&self.rope
+	}
+
+	pub fn into_rope(self) -> Rope {
+		self.rope
+	}
+}
+
+impl Iterator for Replay<'_> {
+	type Item = Vec<Position>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			match self.current {
+				Some((step, remaining)) if remaining > 0 => {
+					self.current = Some((step, remaining - 1));
+					self.rope.execute_step(step);
+					return Some(self.rope.knots.clone());
+				}
+				_ => {
+					let motion = self.motions.next()?;
+					self.current = Some((translation_step(motion), motion.steps()));
+				}
+			}
+		}
 	}
 }
 
+/// Draws knots as `H`, `1`..`9` and `T`, with the start as `s`, lower knots drawn on top.
+/// A single character can't number any further, so knots from 10 up to the tail are all `#`.
+pub fn render_knots(knots: &[Position]) -> String {
+	render(knots.iter().copied(), |position| {
+		match knots.iter().position(|knot| *knot == position) {
+			Some(0) => 'H',
+			Some(i) if i == knots.len() - 1 => 'T',
+			Some(i) if i < 10 => char::from_digit(i as u32, 10).unwrap(),
+			Some(_) => '#',
+			None if position == (0, 0) => 's',
+			None => '.',
+		}
+	})
+}
+
+/// Renders the bounding box of `positions` and the origin, with y increasing upwards
+fn render(positions: impl Iterator<Item = Position>, cell: impl Fn(Position) -> char) -> String {
+	let (xmin, xmax, ymin, ymax) = positions
+		.fold((0, 0, 0, 0), |(xmin, xmax, ymin, ymax), (x, y)| {
+			(xmin.min(x), xmax.max(x), ymin.min(y), ymax.max(y))
+		});
+
+	(ymin..=ymax)
+		.rev()
+		.map(|y| (xmin..=xmax).map(|x| cell((x, y))).collect::<String>())
+		.collect::<Vec<String>>()
+		.join("\n")
+}
+
 fn tail_move(head: Position, tail: Position) -> Option<Position> {
 	tail_movement(head, tail).map(|position| translate(tail, position))
 }
@@ -198,6 +264,10 @@ fn translation_step(motion: &Motion) -> Position {
 		Motion::Down(_) => (0, -1),
 		Motion::Left(_) => (-1, 0),
 		Motion::Right(_) => (1, 0),
+		Motion::UpLeft(_) => (-1, 1),
+		Motion::UpRight(_) => (1, 1),
+		Motion::DownLeft(_) => (-1, -1),
+		Motion::DownRight(_) => (1, -1),
 	}
 }
 
@@ -205,19 +275,30 @@ fn parse_motions(input: &str) -> IResult<&str, Vec<Motion>> {
 	separated_list1(line_ending, parse_motion)(input)
 }
 
+/// Parses `U 4` style motions, diagonals written as `UL`, `UR`, `DL` and `DR`
 fn parse_motion(input: &str) -> IResult<&str, Motion> {
-	let (input, (direction, steps)) = separated_pair(one_of("UDLR"), char(' '), nom_u8)(input)?;
-	match direction {
-		'U' => Ok((input, Motion::Up(steps))),
-		'D' => Ok((input, Motion::Down(steps))),
-		'L' => Ok((input, Motion::Left(steps))),
-		_ => Ok((input, Motion::Right(steps))),
-	}
+	let (input, (direction, steps)) = separated_pair(direction, char(' '), nom_u32)(input)?;
+	Ok((input, direction(steps)))
+}
+
+fn direction(input: &str) -> IResult<&str, fn(u32) -> Motion> {
+	alt((
+		value(Motion::UpLeft as fn(u32) -> Motion, tag("UL")),
+		value(Motion::UpRight as fn(u32) -> Motion, tag("UR")),
+		value(Motion::DownLeft as fn(u32) -> Motion, tag("DL")),
+		value(Motion::DownRight as fn(u32) -> Motion, tag("DR")),
+		value(Motion::Up as fn(u32) -> Motion, tag("U")),
+		value(Motion::Down as fn(u32) -> Motion, tag("D")),
+		value(Motion::Left as fn(u32) -> Motion, tag("L")),
+		value(Motion::Right as fn(u32) -> Motion, tag("R")),
+	))(input)
 }
 
 #[cfg(test)]
 mod test {
-	use crate::day9::{gen, parse_motion, parse_motions, solve_part1, solve_part2, Motion};
+	use crate::day9::{
+		gen, parse_motion, parse_motions, render_knots, solve_part1, solve_part2, Motion, Rope,
+	};
 
 	const EXAMPLE: &str = r"R 4
 U 4
@@ -243,6 +324,12 @@ U 20";
 		assert_eq!(Ok(("", Motion::Down(2))), parse_motion("D 2"));
 		assert_eq!(Ok(("", Motion::Left(3))), parse_motion("L 3"));
 		assert_eq!(Ok(("", Motion::Right(4))), parse_motion("R 4"));
+		assert_eq!(Ok(("", Motion::UpLeft(5))), parse_motion("UL 5"));
+		assert_eq!(Ok(("", Motion::DownRight(300))), parse_motion("DR 300"));
+		assert_eq!(
+			Ok(("", Motion::Right(4_000_000_000))),
+			parse_motion("R 4000000000")
+		);
 
 		assert!(parse_motion("G 4").is_err());
 		assert!(parse_motion("U -1").is_err());
@@ -279,4 +366,85 @@ U 20";
 		let motions = gen(EXAMPLE2);
 		assert_eq!(36, solve_part2(&motions))
 	}
+
+	#[test]
+	fn visited_by_every_knot() {
+		let mut rope = Rope::new(10);
+		gen(EXAMPLE2).iter().for_each(|motion| rope.advance(motion));
+
+		let counts = rope.visited_counts();
+		assert_eq!(10, counts.len());
+		assert_eq!(36, counts[9]);
+		assert_eq!(rope.tail_visited(), counts[9]);
+
+		// The second knot is the tail of a two knot rope following the same head
+		let mut short = Rope::new(2);
+		gen(EXAMPLE2)
+			.iter()
+			.for_each(|motion| short.advance(motion));
+		assert_eq!(short.visited(1), rope.visited(1));
+		assert_eq!(short.visited(0), rope.visited(0));
+	}
+
+	#[test]
+	fn diagonal_motions() {
+		let mut rope = Rope::new(3);
+		rope.advance(&Motion::UpRight(3));
+		assert_eq!(&[(3, 3), (2, 2), (1, 1)], rope.positions());
+		assert_eq!(vec![4, 3, 2], rope.visited_counts());
+
+		rope.advance(&Motion::DownLeft(1));
+		assert_eq!(&[(2, 2), (2, 2), (1, 1)], rope.positions());
+	}
+
+	#[test]
+	fn single_knot() {
+		let mut rope = Rope::new(1);
+		rope.advance(&Motion::Left(3));
+		assert_eq!((-3, 0), rope.tail());
+		assert_eq!(4, rope.tail_visited());
+	}
+
+	#[test]
+	fn replaying_steps() {
+		let motions = gen(EXAMPLE);
+		let frames: Vec<Vec<(i32, i32)>> = Rope::new(2).replay(&motions).collect();
+		assert_eq!(24, frames.len());
+		assert_eq!(vec![(1, 0), (0, 0)], frames[0]);
+		assert_eq!(vec![(4, 0), (3, 0)], frames[3]);
+		assert_eq!(vec![(4, 1), (3, 0)], frames[4]);
+
+		let mut rope = Rope::new(2);
+		motions.iter().for_each(|motion| rope.advance(motion));
+		let mut replay = Rope::new(2).replay(&motions);
+		assert_eq!(Some(rope.positions().to_vec()), replay.by_ref().last());
+		assert_eq!(rope, replay.into_rope());
+	}
+
+	#[test]
+	fn rendering() {
+		assert_eq!("s..TH", render_knots(&[(4, 0), (3, 0)]));
+		assert_eq!("H.\n.1\nT.", render_knots(&[(0, 2), (1, 1), (0, 0)]));
+		assert_eq!(".H\nTs", render_knots(&[(0, 1), (-1, 0)]));
+		// Knots 10 and 11 don't pass for 0 and 1
+		let long: Vec<(i32, i32)> = (0..13).map(|x| (12 - x, 1)).collect();
+		assert_eq!("T##987654321H\ns............", render_knots(&long));
+
+		let mut rope = Rope::new(2);
+		gen(EXAMPLE).iter().for_each(|motion| rope.advance(motion));
+		assert_eq!(
+			Some("..##.\n...##\n.####\n....#\ns###.".to_string()),
+			rope.render_visited(1)
+		);
+		assert_eq!(None, rope.render_visited(2));
+
+		let frame = Rope::new(10).replay(&gen(EXAMPLE2)).last().unwrap();
+		let rendered = render_knots(&frame);
+		let lines: Vec<&str> = rendered.lines().collect();
+		assert_eq!(
+			vec!["H...........", "1...........", "2..........."],
+			lines[..3]
+		);
+		assert_eq!(Some(&"...........s"), lines.last());
+	}
 }
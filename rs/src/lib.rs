@@ -1,15 +1,15 @@
 mod day1;
 mod day2;
-mod day3;
-mod day4;
-mod day5;
-mod day6;
-mod day7;
-mod day8;
-mod day9;
-mod day10;
-mod day11;
-mod day12;
+pub mod day3;
+pub mod day4;
+pub mod day5;
+pub mod day6;
+pub mod day7;
+pub mod day8;
+pub mod day9;
+pub mod day10;
+pub mod day11;
+pub mod day12;
 mod day13;
 mod day14;
 mod day15;
@@ -23,6 +23,7 @@ mod day23;
 mod day24;
 mod day25;
 mod day19;
+mod netpbm;
 
 use yaah::{aoc_lib, aoc_year};
 
@@ -0,0 +1,33 @@
+//! Helpers shared by the plain (ASCII) Netpbm images some days export
+
+use std::fmt::Display;
+
+/// Plain Netpbm lines shouldn't be longer than this
+pub const LINE_LIMIT: usize = 70;
+
+/// Space separated, wrapping onto a new line rather than going past [LINE_LIMIT]
+pub fn join<T: Display>(values: impl Iterator<Item = T>) -> String {
+	wrap(values, LINE_LIMIT)
+}
+
+/// Comment lines ending in a newline, each one `#` and its words kept within [LINE_LIMIT]
+pub fn comment(text: &str) -> String {
+	wrap(text.split_whitespace(), LINE_LIMIT - "# ".len())
+		.lines()
+		.map(|line| format!("# {line}\n"))
+		.collect()
+}
+
+fn wrap<T: Display>(values: impl Iterator<Item = T>, limit: usize) -> String {
+	let mut lines: Vec<String> = vec![];
+	for value in values.map(|v| v.to_string()) {
+		match lines.last_mut() {
+			Some(line) if line.len() + 1 + value.len() <= limit => {
+				line.push(' ');
+				line.push_str(&value);
+			}
+			_ => lines.push(value),
+		}
+	}
+	lines.join("\n")
+}